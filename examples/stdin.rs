@@ -9,11 +9,12 @@ extern crate structopt;
 
 use std::io::{self, BufRead};
 use std::env;
+use std::fs::File;
 use std::path::PathBuf;
 use tempdir::TempDir;
 use structopt::StructOpt;
 
-use manga_rs::{SimpleAdapter, AsciiTester, MemoryStore, Mdbm, process_item, check_item};
+use manga_rs::{SimpleAdapter, AsciiTester, AsciiTesterConfig, MemoryStore, Mdbm, process_iter, check_iter};
 use gnip_twitter_stream::MinimalTweet;
 use hit_manager::DbAdapter;
 
@@ -40,6 +41,10 @@ struct Opt {
     /// Mdbm chunk size
     #[structopt(short = "s", long = "size", default_value = "2000000")]
     mdbm_size: usize,
+
+    /// A JSON file with tester settings (see `AsciiTesterConfig`)
+    #[structopt(short = "c", long = "tester-config", parse(from_os_str))]
+    tester_config: Option<PathBuf>,
 }
 
 fn main() {
@@ -48,7 +53,15 @@ fn main() {
     let stdin = io::stdin();
     //let mut adapter = SimpleAdapter::new();
     let mut adapter = DbAdapter::new();
-    let mut tester = AsciiTester::default();
+    let config = match opt.tester_config {
+        Some(ref p) => {
+            let file = File::open(p).expect("failed to open tester config");
+            serde_json::from_reader::<_, AsciiTesterConfig>(file)
+                .expect("failed to parse tester config")
+        }
+        None => AsciiTesterConfig::default(),
+    };
+    let mut tester = AsciiTester::new(config);
     let path = match opt.path {
         Some(p) => p,
         None => TempDir::new("anagrams_rs").unwrap().path().to_owned(),
@@ -57,23 +70,23 @@ fn main() {
     //let mut store = Mdbm::new(&path, opt.mdbm_size);
     let mut store = MemoryStore::new();
 
-    for item in stdin.lock().lines() {
-        let raw_item = item.expect("erorr in stream");
-        let item = match serde_json::from_str::<MinimalTweet>(&raw_item) {
-            Ok(item) => item,
-            Err(e) => {
-                println!("error decoding item {:?}", e);
-                break
-            }
-        };
-        if item.text.len() < MINIMUM_STRING_LEN { continue }
-        if opt.no_write {
-            check_item(item, &mut store, &mut adapter, &mut tester);
-        } else {
-            process_item(item, &mut store, &mut adapter, &mut tester);
-        }
-    }
+    let items = stdin.lock().lines()
+        .map(|item| {
+            let raw_item = item.expect("erorr in stream");
+            serde_json::from_str::<MinimalTweet>(&raw_item)
+                .map_err(|e| println!("error decoding item {:?}", e))
+        })
+        .take_while(Result::is_ok)
+        .flat_map(Result::ok);
+    let mut filter = |item: &MinimalTweet| item.text.len() >= MINIMUM_STRING_LEN;
+
+    let summary = if opt.no_write {
+        check_iter(items, Some(&mut filter), &mut store, &mut adapter, &mut tester)
+    } else {
+        process_iter(items, Some(&mut filter), &mut store, &mut adapter, &mut tester)
+    };
 
+    eprintln!("{:?}", summary);
     eprintln!("found {} hits", adapter.count());
     if opt.verbose {
         let mut hits = adapter.get_hits(None, 50, None);
@@ -0,0 +1,116 @@
+extern crate manga_rs;
+extern crate gnip_twitter_stream;
+#[macro_use]
+extern crate serde_json;
+
+#[macro_use]
+extern crate structopt;
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+use gnip_twitter_stream::MinimalTweet;
+use manga_rs::{AsciiFingerprint, StoreReader, Chunk, GdbmChunk, LogChunk, convert_dbm_chunks};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "tools")]
+enum Opt {
+    /// Convert the gdbm chunks of an Mdbm into a LogStore
+    #[structopt(name = "convert")]
+    Convert {
+        /// The Mdbm directory
+        #[structopt(parse(from_os_str))]
+        from: PathBuf,
+
+        /// The directory for the new LogStore
+        #[structopt(parse(from_os_str))]
+        to: PathBuf,
+    },
+
+    /// Print the number of entries in each chunk of an Mdbm
+    #[structopt(name = "chunks")]
+    Chunks {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// Read a LogStore, instead of gdbm chunks
+        #[structopt(long = "log")]
+        log: bool,
+    },
+
+    /// Print the candidates in an Mdbm as JSON lines
+    #[structopt(name = "dump")]
+    Dump {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// Read a LogStore, instead of gdbm chunks
+        #[structopt(long = "log")]
+        log: bool,
+
+        /// Only print the candidates with these fingerprints, given as
+        /// any text with the same letters
+        fingerprints: Vec<String>,
+    },
+}
+
+type Store<C> = StoreReader<MinimalTweet, C>;
+
+/// Opens a store for reading, so that it can be inspected while in use.
+fn open_store<C: Chunk>(path: &Path) -> Store<C> {
+    if !path.is_dir() {
+        eprintln!("no such directory {:?}", path);
+        ::std::process::exit(1);
+    }
+    Store::open(path).expect("failed to open store")
+}
+
+fn print_chunks<C: Chunk>(path: &Path) {
+    let store: Store<C> = open_store(path);
+    let stats = store.chunk_stats().expect("failed to read chunks");
+    let mut total = 0;
+    for chunk in stats.iter() {
+        println!("{}\t{}\t{} entries\t{} bytes",
+                 chunk.path.file_name().unwrap().to_string_lossy(),
+                 chunk.created_at, chunk.entries, chunk.bytes);
+        total += chunk.entries;
+    }
+    println!("{} chunks, {} entries", stats.len(), total);
+}
+
+fn dump<C: Chunk>(path: &Path, fingerprints: &[String]) {
+    let store: Store<C> = open_store(path);
+    let wanted = fingerprints.iter()
+        .map(|s| AsciiFingerprint::new(s))
+        .collect::<Vec<_>>();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for entry in store.entries() {
+        let (key, values) = entry.expect("failed to read entry");
+        let fingerprint = match AsciiFingerprint::from_bytes(&key) {
+            Some(fingerprint) => fingerprint,
+            None => continue,
+        };
+        if !wanted.is_empty() && !wanted.contains(&fingerprint) { continue }
+        let texts = values.iter().map(|t| t.text.as_str()).collect::<Vec<_>>();
+        let line = json!({ "fingerprint": fingerprint.to_string(), "values": texts });
+        writeln!(stdout, "{}", line).expect("failed to write to stdout");
+    }
+}
+
+fn main() {
+    match Opt::from_args() {
+        Opt::Convert { from, to } => {
+            let converted = convert_dbm_chunks(&from, &to).unwrap_or_else(|e| {
+                eprintln!("failed to convert chunks: {}", e);
+                ::std::process::exit(1)
+            });
+            println!("converted {} chunks", converted);
+        }
+        Opt::Chunks { path, log: false } => print_chunks::<GdbmChunk>(&path),
+        Opt::Chunks { path, log: true } => print_chunks::<LogChunk>(&path),
+        Opt::Dump { path, log: false, fingerprints } => dump::<GdbmChunk>(&path, &fingerprints),
+        Opt::Dump { path, log: true, fingerprints } => dump::<LogChunk>(&path, &fingerprints),
+    }
+}
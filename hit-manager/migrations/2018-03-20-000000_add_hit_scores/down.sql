@@ -0,0 +1,9 @@
+DROP INDEX hitscore_idx;
+
+ALTER TABLE hits
+  DROP COLUMN hitscore,
+  DROP COLUMN char_distance,
+  DROP COLUMN word_distance,
+  DROP COLUMN shared_words,
+  DROP COLUMN letter_count,
+  DROP COLUMN word_count_diff
@@ -0,0 +1,9 @@
+ALTER TABLE hits
+  ADD COLUMN hitscore DOUBLE PRECISION,
+  ADD COLUMN char_distance INTEGER,
+  ADD COLUMN word_distance INTEGER,
+  ADD COLUMN shared_words DOUBLE PRECISION,
+  ADD COLUMN letter_count INTEGER,
+  ADD COLUMN word_count_diff INTEGER;
+
+CREATE INDEX hitscore_idx ON hits (hitscore)
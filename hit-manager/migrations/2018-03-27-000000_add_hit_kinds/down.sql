@@ -0,0 +1,4 @@
+DROP INDEX hitkind_idx;
+
+ALTER TABLE hits
+  DROP COLUMN hitkind
@@ -0,0 +1,4 @@
+ALTER TABLE hits
+  ADD COLUMN hitkind INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX hitkind_idx ON hits (hitkind)
@@ -20,11 +20,11 @@ use diesel::pg::PgConnection;
 
 use dotenv::dotenv;
 
-use manga_rs::{Adapter, Tester};
+use manga_rs::{Adapter, Tester, MatchScore, LetterOffset, Fingerprint};
 use gnip_twitter_stream::MinimalTweet;
 
 use models::NewHit;
-pub use models::{Hit, JoinedHit, HitStatus, Tweet};
+pub use models::{Hit, JoinedHit, HitStatus, HitKind, Tweet};
 
 pub fn establish_connection() -> PgConnection {
     dotenv().ok();
@@ -33,24 +33,33 @@ pub fn establish_connection() -> PgConnection {
     PgConnection::establish(&database_url).expect(&format!("Error connecting to {}", database_url))
 }
 
-pub fn create_hit<H: AsRef<[u8]>>(
+pub fn create_hit<H: Fingerprint>(
     conn: &PgConnection,
     one: &str,
     two: &str,
     one_id: u64,
     two_id: u64,
     hithash: &H,
+    hitkind: HitKind,
+    score: &MatchScore,
 ) -> QueryResult<usize> {
     use schema::{hits, tweets};
     let hitdate = SystemTime::now();
     let status = HitStatus::New;
+    let hitlen = hithash.len() as i32;
     let hithash = hithash.as_ref().to_owned();
-    let hitlen = hithash.iter().map(|i| *i as i32).sum();
     let new_hit = NewHit {
         hitdate,
         status,
         hithash,
         hitlen,
+        hitscore: Some(score.rank()),
+        char_distance: Some(score.char_distance as i32),
+        word_distance: Some(score.word_distance as i32),
+        shared_words: Some(score.shared_words),
+        letter_count: Some(score.letter_count as i32),
+        word_count_diff: Some(score.word_count_diff as i32),
+        hitkind,
     };
 
     let hit: Hit = diesel::insert_into(hits::table)
@@ -77,6 +86,8 @@ fn get_hit(conn: &PgConnection, with_id: i32) -> QueryResult<Hit> {
     hits.find(with_id).get_result(conn)
 }
 
+/// Returns up to `max_results` hits with ids greater than `newer_than`,
+/// in id order, so that the largest id can be passed as the next `newer_than`.
 pub fn get_hits<T, C, N>(
     conn: &PgConnection,
     of_status: T,
@@ -96,27 +107,60 @@ where
     let result = if let Some(stat) = of_status.into() {
         hits.filter(status.eq(stat))
             .filter(id.gt(newer_than.into().unwrap_or(0)))
+            .order(id)
             .limit(max_results)
             .load::<Hit>(conn)
     } else {
         hits.filter(id.gt(newer_than.into().unwrap_or(0)))
+            .order(id)
             .limit(max_results)
             .load::<Hit>(conn)
     };
 
-    let result: QueryResult<Vec<JoinedHit>> = result.map(|hs| {
-        hs.into_iter().map(|hit| {
-            let mut tweets = Tweet::belonging_to(&hit)
-                .load::<Tweet>(conn)
-                .expect("missing tweets is bad");
-            JoinedHit {
-                hit: hit,
-                two: tweets.pop().unwrap(),
-                one: tweets.pop().unwrap(),
-            }
-        }).collect()
-    });
-    result
+    result.map(|hs| join_tweets(conn, hs))
+}
+
+/// Returns up to `max_results` hits, best scoring first. Hits saved before
+/// scoring was added come last.
+pub fn get_best_hits<T, C>(
+    conn: &PgConnection,
+    of_status: T,
+    max_results: C,
+) -> QueryResult<Vec<JoinedHit>>
+where
+    T: Into<Option<HitStatus>>,
+    C: Into<Option<usize>>,
+{
+    use schema::hits::dsl::*;
+    let max_results = max_results.into()
+        .unwrap_or(i64::max_value() as usize)
+        .min(i64::max_value() as usize) as i64;
+
+    let result = if let Some(stat) = of_status.into() {
+        hits.filter(status.eq(stat))
+            .order((hitscore.desc().nulls_last(), id))
+            .limit(max_results)
+            .load::<Hit>(conn)
+    } else {
+        hits.order((hitscore.desc().nulls_last(), id))
+            .limit(max_results)
+            .load::<Hit>(conn)
+    };
+
+    result.map(|hs| join_tweets(conn, hs))
+}
+
+fn join_tweets(conn: &PgConnection, hits: Vec<Hit>) -> Vec<JoinedHit> {
+    hits.into_iter().map(|hit| {
+        let mut tweets = Tweet::belonging_to(&hit)
+            .load::<Tweet>(conn)
+            .expect("missing tweets is bad");
+        JoinedHit {
+            hit: hit,
+            two: tweets.pop().unwrap(),
+            one: tweets.pop().unwrap(),
+        }
+    }).collect()
 }
 
 pub fn count_hits<T>(conn: &PgConnection, of_status: T) -> QueryResult<usize>
@@ -154,17 +198,34 @@ impl DbAdapter {
     {
         get_hits(&self.connection, status, max_results, newer_than).unwrap_or_default()
     }
+
+    pub fn get_best_hits<T, C>(&self, status: T, max_results: C) -> Vec<JoinedHit>
+    where
+        T: Into<Option<HitStatus>>,
+        C: Into<Option<usize>>,
+    {
+        get_best_hits(&self.connection, status, max_results).unwrap_or_default()
+    }
 }
 
 impl<TE> Adapter<MinimalTweet, TE> for DbAdapter
 where
     TE: Tester<MinimalTweet>,
-    TE::Fingerprint: AsRef<[u8]>,
+    TE::Fingerprint: Fingerprint,
 {
-    fn handle_match(&mut self, p1: &MinimalTweet, p2: &MinimalTweet, hash: &TE::Fingerprint) {
+    fn handle_match(&mut self, p1: &MinimalTweet, p2: &MinimalTweet,
+                    hash: &TE::Fingerprint, score: &MatchScore) {
         if let Err(e) = create_hit(&self.connection, &p1.text, &p2.text,
-                                   p1.id(), p2.id(), hash) {
+                                   p1.id(), p2.id(), hash, HitKind::Exact, score) {
             eprintln!("error handling match: {:?}", e);
         }
     }
+
+    fn handle_near_match(&mut self, p1: &MinimalTweet, p2: &MinimalTweet,
+                         hash: &TE::Fingerprint, _offset: LetterOffset, score: &MatchScore) {
+        if let Err(e) = create_hit(&self.connection, &p1.text, &p2.text,
+                                   p1.id(), p2.id(), hash, HitKind::Near, score) {
+            eprintln!("error handling near match: {:?}", e);
+        }
+    }
 }
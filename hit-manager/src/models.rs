@@ -43,6 +43,33 @@ impl FromSql<Int4, Pg> for HitStatus {
     }
 }
 
+/// Whether a hit is an exact anagram, or differs by a letter.
+#[derive(Debug, Copy, Clone, PartialEq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[sql_type = "Int4"]
+#[serde(rename_all="snake_case")]
+pub enum HitKind {
+    Exact = 0,
+    Near = 1,
+}
+
+impl ToSql<Int4, Pg> for HitKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let val = *self as i32;
+        <i32 as ToSql<Int4, Pg>>::to_sql(&val, out)
+    }
+}
+
+impl FromSql<Int4, Pg> for HitKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let as_int = <i32 as FromSql<Int4, Pg>>::from_sql(bytes)?;
+        match as_int {
+            0 => Ok(HitKind::Exact),
+            1 => Ok(HitKind::Near),
+            other => Err(format!("illegal kind variant {}", other).into()),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, FromSqlRow, AsExpression, Serialize, Deserialize)]
 #[sql_type = "Int4"]
 pub enum TweetStatus {
@@ -77,6 +104,13 @@ pub struct Hit {
     pub hitdate: SystemTime,
     pub hithash: Vec<u8>,
     pub hitlen: i32,
+    pub hitscore: Option<f64>,
+    pub char_distance: Option<i32>,
+    pub word_distance: Option<i32>,
+    pub shared_words: Option<f64>,
+    pub letter_count: Option<i32>,
+    pub word_count_diff: Option<i32>,
+    pub hitkind: HitKind,
 }
 
 #[derive(Insertable)]
@@ -86,6 +120,13 @@ pub struct NewHit {
     pub hitdate: SystemTime,
     pub hithash: Vec<u8>,
     pub hitlen: i32,
+    pub hitscore: Option<f64>,
+    pub char_distance: Option<i32>,
+    pub word_distance: Option<i32>,
+    pub shared_words: Option<f64>,
+    pub letter_count: Option<i32>,
+    pub word_count_diff: Option<i32>,
+    pub hitkind: HitKind,
 }
 
 #[derive(Debug, Clone, Identifiable, Queryable, Associations, AsChangeset, Insertable, Serialize, Deserialize)]
@@ -5,6 +5,13 @@ table! {
         hitdate -> Timestamp,
         hithash -> Bytea,
         hitlen -> Int4,
+        hitscore -> Nullable<Float8>,
+        char_distance -> Nullable<Int4>,
+        word_distance -> Nullable<Int4>,
+        shared_words -> Nullable<Float8>,
+        letter_count -> Nullable<Int4>,
+        word_count_diff -> Nullable<Int4>,
+        hitkind -> Int4,
     }
 }
 
@@ -0,0 +1,613 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use serde_json;
+
+use anagrams::{AsStr, Adapter, Tester, MatchScore, LetterOffset, Rejection};
+
+const DEFAULT_TOP_FINGERPRINTS: usize = 20;
+
+/// An adapter which records how fingerprints are distributed.
+///
+/// This keeps a count for every distinct fingerprint it sees, so it is
+/// intended for analysing a corpus, rather than for long-running use.
+#[derive(Debug, Clone)]
+pub struct StatsAdapter {
+    fingerprints: HashMap<String, usize>,
+    lengths: BTreeMap<usize, LengthStats>,
+    rejections: BTreeMap<Rejection, usize>,
+    items: usize,
+    collisions: usize,
+    possible_matches: usize,
+    hits: usize,
+    top_n: usize,
+}
+
+/// Counts for the items with fingerprints of a given length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LengthStats {
+    pub items: usize,
+    /// Items whose fingerprint had been seen before.
+    pub collisions: usize,
+    pub collision_rate: f64,
+}
+
+/// A summary of the fingerprints seen by a `StatsAdapter`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub items: usize,
+    pub distinct_fingerprints: usize,
+    /// Items whose fingerprint had been seen before.
+    pub collisions: usize,
+    pub collision_rate: f64,
+    pub possible_matches: usize,
+    pub hits: usize,
+    /// Stats for each fingerprint length (in letters).
+    pub lengths: BTreeMap<usize, LengthStats>,
+    /// How many possible matches were rejected for each reason.
+    pub rejections: BTreeMap<Rejection, usize>,
+    /// The most common fingerprints, and how many times each was seen.
+    pub top_fingerprints: Vec<(String, usize)>,
+}
+
+impl StatsAdapter {
+    pub fn new() -> Self {
+        StatsAdapter::with_top_fingerprints(DEFAULT_TOP_FINGERPRINTS)
+    }
+
+    /// Creates an adapter which reports the `top_n` most common fingerprints.
+    pub fn with_top_fingerprints(top_n: usize) -> Self {
+        StatsAdapter {
+            fingerprints: HashMap::new(),
+            lengths: BTreeMap::new(),
+            rejections: BTreeMap::new(),
+            items: 0,
+            collisions: 0,
+            possible_matches: 0,
+            hits: 0,
+            top_n,
+        }
+    }
+
+    pub fn report(&self) -> StatsReport {
+        let mut top_fingerprints = self.fingerprints.iter()
+            .filter(|&(_, count)| *count > 1)
+            .map(|(fingerprint, count)| (fingerprint.clone(), *count))
+            .collect::<Vec<_>>();
+        top_fingerprints.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_fingerprints.truncate(self.top_n);
+
+        let lengths = self.lengths.iter()
+            .map(|(len, stats)| {
+                let collision_rate = rate(stats.collisions, stats.items);
+                (*len, LengthStats { collision_rate, ..*stats })
+            })
+            .collect();
+
+        StatsReport {
+            items: self.items,
+            distinct_fingerprints: self.fingerprints.len(),
+            collisions: self.collisions,
+            collision_rate: rate(self.collisions, self.items),
+            possible_matches: self.possible_matches,
+            hits: self.hits,
+            lengths,
+            rejections: self.rejections.clone(),
+            top_fingerprints,
+        }
+    }
+
+    /// Writes the report as JSON.
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.report())?;
+        Ok(())
+    }
+
+    /// Writes the report as JSON to the file at `path`.
+    pub fn dump_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        self.write_json(file)
+    }
+}
+
+impl Default for StatsAdapter {
+    fn default() -> Self {
+        StatsAdapter::new()
+    }
+}
+
+fn rate(count: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { count as f64 / total as f64 }
+}
+
+impl<T, TE> Adapter<T, TE> for StatsAdapter
+    where TE: Tester<T>,
+          TE::Fingerprint: Display,
+{
+    fn fingerprinted(&mut self, _item: &T, hash: &TE::Fingerprint) {
+        let fingerprint = hash.to_string();
+        let len = fingerprint.chars().count();
+        let count = self.fingerprints.entry(fingerprint).or_insert(0);
+        *count += 1;
+
+        let stats = self.lengths.entry(len).or_default();
+        stats.items += 1;
+        self.items += 1;
+        if *count > 1 {
+            stats.collisions += 1;
+            self.collisions += 1;
+        }
+    }
+
+    fn possible_match(&mut self, _p1: &T, _p2: &T) {
+        self.possible_matches += 1;
+    }
+
+    fn rejected(&mut self, _p1: &T, _p2: &T, reason: Rejection) {
+        *self.rejections.entry(reason).or_insert(0) += 1;
+    }
+
+    fn handle_match(&mut self, _p1: &T, _p2: &T, _hash: &TE::Fingerprint, _score: &MatchScore) {
+        self.hits += 1;
+    }
+}
+
+/// Implements `Adapter` for a tuple of adapters, forwarding each event to
+/// every element in order.
+macro_rules! tuple_adapter {
+    ($($name:ident . $idx:tt),+) => {
+        impl<T, TE, $($name),+> Adapter<T, TE> for ($($name,)+)
+            where TE: Tester<T>,
+                  $($name: Adapter<T, TE>),+
+        {
+            fn will_check(&mut self, item: &T) {
+                $( self.$idx.will_check(item); )+
+            }
+
+            fn fingerprinted(&mut self, item: &T, hash: &TE::Fingerprint) {
+                $( self.$idx.fingerprinted(item, hash); )+
+            }
+
+            fn possible_match(&mut self, p1: &T, p2: &T) {
+                $( self.$idx.possible_match(p1, p2); )+
+            }
+
+            fn rejected(&mut self, p1: &T, p2: &T, reason: Rejection) {
+                $( self.$idx.rejected(p1, p2, reason); )+
+            }
+
+            fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                            score: &MatchScore) {
+                $( self.$idx.handle_match(p1, p2, hash, score); )+
+            }
+
+            fn handle_near_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                                 offset: LetterOffset, score: &MatchScore) {
+                $( self.$idx.handle_near_match(p1, p2, hash, offset, score); )+
+            }
+
+            fn handle_subset_match(&mut self, p1: &T, p2: &T, remainder: &TE::Fingerprint) {
+                $( self.$idx.handle_subset_match(p1, p2, remainder); )+
+            }
+        }
+    };
+}
+
+tuple_adapter!(A.0, B.1);
+tuple_adapter!(A.0, B.1, C.2);
+tuple_adapter!(A.0, B.1, C.2, D.3);
+
+/// Forwards each event to every adapter in the `Vec`, in order.
+///
+/// A `Vec<Box<Adapter<T, TE>>>` can hold adapters of different types.
+impl<T, TE, A> Adapter<T, TE> for Vec<A>
+    where TE: Tester<T>,
+          A: Adapter<T, TE>,
+{
+    fn will_check(&mut self, item: &T) {
+        for adapter in self.iter_mut() {
+            adapter.will_check(item);
+        }
+    }
+
+    fn fingerprinted(&mut self, item: &T, hash: &TE::Fingerprint) {
+        for adapter in self.iter_mut() {
+            adapter.fingerprinted(item, hash);
+        }
+    }
+
+    fn possible_match(&mut self, p1: &T, p2: &T) {
+        for adapter in self.iter_mut() {
+            adapter.possible_match(p1, p2);
+        }
+    }
+
+    fn rejected(&mut self, p1: &T, p2: &T, reason: Rejection) {
+        for adapter in self.iter_mut() {
+            adapter.rejected(p1, p2, reason);
+        }
+    }
+
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, score: &MatchScore) {
+        for adapter in self.iter_mut() {
+            adapter.handle_match(p1, p2, hash, score);
+        }
+    }
+
+    fn handle_near_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                         offset: LetterOffset, score: &MatchScore) {
+        for adapter in self.iter_mut() {
+            adapter.handle_near_match(p1, p2, hash, offset, score);
+        }
+    }
+
+    fn handle_subset_match(&mut self, p1: &T, p2: &T, remainder: &TE::Fingerprint) {
+        for adapter in self.iter_mut() {
+            adapter.handle_subset_match(p1, p2, remainder);
+        }
+    }
+}
+
+impl<T, TE, A> Adapter<T, TE> for Box<A>
+    where TE: Tester<T>,
+          A: Adapter<T, TE> + ?Sized,
+{
+    fn will_check(&mut self, item: &T) {
+        (**self).will_check(item)
+    }
+
+    fn fingerprinted(&mut self, item: &T, hash: &TE::Fingerprint) {
+        (**self).fingerprinted(item, hash)
+    }
+
+    fn possible_match(&mut self, p1: &T, p2: &T) {
+        (**self).possible_match(p1, p2)
+    }
+
+    fn rejected(&mut self, p1: &T, p2: &T, reason: Rejection) {
+        (**self).rejected(p1, p2, reason)
+    }
+
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, score: &MatchScore) {
+        (**self).handle_match(p1, p2, hash, score)
+    }
+
+    fn handle_near_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                         offset: LetterOffset, score: &MatchScore) {
+        (**self).handle_near_match(p1, p2, hash, offset, score)
+    }
+
+    fn handle_subset_match(&mut self, p1: &T, p2: &T, remainder: &TE::Fingerprint) {
+        (**self).handle_subset_match(p1, p2, remainder)
+    }
+}
+
+/// An adapter which only passes on the matches accepted by a predicate.
+///
+/// The predicate is called with both items and the match's score, and gates
+/// `handle_match` and `handle_near_match`. Every other event is passed on
+/// unchanged.
+pub struct FilterAdapter<A, F> {
+    inner: A,
+    predicate: F,
+}
+
+impl<A, F> FilterAdapter<A, F> {
+    pub fn new(inner: A, predicate: F) -> Self {
+        FilterAdapter { inner, predicate }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+}
+
+impl<T, TE, A, F> Adapter<T, TE> for FilterAdapter<A, F>
+    where TE: Tester<T>,
+          A: Adapter<T, TE>,
+          F: FnMut(&T, &T, &MatchScore) -> bool,
+{
+    fn will_check(&mut self, item: &T) {
+        self.inner.will_check(item)
+    }
+
+    fn fingerprinted(&mut self, item: &T, hash: &TE::Fingerprint) {
+        self.inner.fingerprinted(item, hash)
+    }
+
+    fn possible_match(&mut self, p1: &T, p2: &T) {
+        self.inner.possible_match(p1, p2)
+    }
+
+    fn rejected(&mut self, p1: &T, p2: &T, reason: Rejection) {
+        self.inner.rejected(p1, p2, reason)
+    }
+
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, score: &MatchScore) {
+        if (self.predicate)(p1, p2, score) {
+            self.inner.handle_match(p1, p2, hash, score)
+        }
+    }
+
+    fn handle_near_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                         offset: LetterOffset, score: &MatchScore) {
+        if (self.predicate)(p1, p2, score) {
+            self.inner.handle_near_match(p1, p2, hash, offset, score)
+        }
+    }
+
+    fn handle_subset_match(&mut self, p1: &T, p2: &T, remainder: &TE::Fingerprint) {
+        self.inner.handle_subset_match(p1, p2, remainder)
+    }
+}
+
+/// A single hit, as written by `JsonLinesAdapter`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HitRecord {
+    pub p1: String,
+    pub p2: String,
+    /// The fingerprint of `p1`, formatted with `Display`.
+    pub fingerprint: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<MatchScore>,
+    /// For a near anagram, how `p2` differs from `p1`; `None` for an
+    /// exact match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<LetterOffset>,
+}
+
+/// An adapter which appends each hit to a file, as one JSON object per line.
+///
+/// Each line is flushed as soon as it is written. If a maximum size is set,
+/// a full file is renamed with a numbered suffix (`hits.jsonl.1`,
+/// `hits.jsonl.2`, ...) and a new file is started.
+pub struct JsonLinesAdapter {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: Option<u64>,
+    include_scores: bool,
+    hits: usize,
+}
+
+impl JsonLinesAdapter {
+    /// Opens the file at `path` for appending, creating it if necessary.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = open_append(&path)?;
+        let written = file.metadata()?.len();
+        Ok(JsonLinesAdapter {
+            path, file, written,
+            max_bytes: None,
+            include_scores: true,
+            hits: 0,
+        })
+    }
+
+    /// Starts a new file once the current one would exceed `max_bytes`.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Whether to include each hit's `MatchScore`. Defaults to `true`.
+    pub fn include_scores(mut self, include_scores: bool) -> Self {
+        self.include_scores = include_scores;
+        self
+    }
+
+    /// The number of hits written.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn write_record(&mut self, record: &HitRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        if let Some(max) = self.max_bytes {
+            if self.written > 0 && self.written + line.len() as u64 > max {
+                self.rotate()?;
+            }
+        }
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+        self.written += line.len() as u64;
+        self.hits += 1;
+        Ok(())
+    }
+
+    fn write_hit<T, F>(&mut self, p1: &T, p2: &T, hash: &F, score: &MatchScore,
+                       offset: Option<LetterOffset>)
+        where T: AsStr,
+              F: Display,
+    {
+        let record = HitRecord {
+            p1: p1.as_str().to_owned(),
+            p2: p2.as_str().to_owned(),
+            fingerprint: hash.to_string(),
+            timestamp: Utc::now(),
+            score: if self.include_scores { Some(score.clone()) } else { None },
+            offset,
+        };
+        if let Err(e) = self.write_record(&record) {
+            eprintln!("error writing hit to {:?}: {:?}", self.path, e);
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut n = 1;
+        let rotated = loop {
+            let mut name = OsString::from(self.path.as_os_str());
+            name.push(format!(".{}", n));
+            let candidate = PathBuf::from(name);
+            if !candidate.exists() { break candidate }
+            n += 1;
+        };
+        fs::rename(&self.path, &rotated)?;
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl<T, TE> Adapter<T, TE> for JsonLinesAdapter
+    where T: AsStr,
+          TE: Tester<T>,
+          TE::Fingerprint: Display,
+{
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, score: &MatchScore) {
+        self.write_hit(p1, p2, hash, score, None);
+    }
+
+    fn handle_near_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                         offset: LetterOffset, score: &MatchScore) {
+        self.write_hit(p1, p2, hash, score, Some(offset));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use tempdir::TempDir;
+    use super::*;
+    use anagrams::{AsciiTester, SimpleAdapter, MemoryStore, process_item, process_item_near,
+                   process_iter};
+
+    #[test]
+    fn stats() {
+        let items = vec!["dormitory", "dirty room", "Dormitory!", "dormitory",
+                         "I hate being sick", "I hate bieng sick", "dog"];
+        let mut adapter = StatsAdapter::with_top_fingerprints(1);
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        process_iter(items, None, &mut store, &mut adapter, &mut tester);
+
+        let report = adapter.report();
+        assert_eq!(report.items, 7);
+        assert_eq!(report.distinct_fingerprints, 3);
+        assert_eq!(report.collisions, 4);
+        assert_eq!(report.hits, 1);
+        assert_eq!(report.possible_matches, 3);
+        assert_eq!(report.rejections.get(&Rejection::SameLetters), Some(&1));
+        assert_eq!(report.rejections.get(&Rejection::TooSimilar), Some(&1));
+        assert_eq!(report.lengths[&9], LengthStats { items: 4, collisions: 3, collision_rate: 0.75 });
+        assert_eq!(report.lengths[&14].collisions, 1);
+        assert_eq!(report.lengths[&3].items, 1);
+        assert_eq!(report.top_fingerprints, vec![("dimoorrty".to_string(), 4)]);
+
+        let mut json = Vec::new();
+        adapter.write_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["rejections"]["same_letters"], 1);
+        assert_eq!(json["lengths"]["9"]["items"], 4);
+    }
+
+    #[test]
+    fn fan_out() {
+        let items = vec!["dormitory", "dirty room", "astronomer", "moon starer", "dog"];
+        let mut adapter = (SimpleAdapter::new(), StatsAdapter::new());
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        process_iter(items.clone(), None, &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.0.hits().len(), 2);
+        assert_eq!(adapter.1.report().hits, 2);
+
+        let mut adapters: Vec<Box<Adapter<&str, AsciiTester>>> =
+            vec![Box::new(SimpleAdapter::new()), Box::new(StatsAdapter::new())];
+        let mut store = MemoryStore::new();
+        let summary = process_iter(items, None, &mut store, &mut adapters, &mut tester);
+        assert_eq!(summary.hits, 2);
+    }
+
+    #[test]
+    fn filter() {
+        let items = vec!["dormitory", "dirty room", "astronomer", "moon starer"];
+        let inner = (SimpleAdapter::new(), StatsAdapter::new());
+        let mut adapter = FilterAdapter::new(inner, |p1: &&str, _: &&str, _: &MatchScore| {
+            p1.starts_with('m')
+        });
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        process_iter(items, None, &mut store, &mut adapter, &mut tester);
+
+        let (simple, stats) = adapter.into_inner();
+        assert_eq!(simple.hits(), &[("moon starer", "astronomer")]);
+        let report = stats.report();
+        assert_eq!(report.hits, 1);
+        // other events are not filtered
+        assert_eq!(report.possible_matches, 2);
+    }
+
+    fn read_records(path: &Path) -> Vec<HitRecord> {
+        let file = File::open(path).unwrap();
+        BufReader::new(file).lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn json_lines() {
+        let tempdir = TempDir::new("jsonl_test").unwrap();
+        let path = tempdir.path().join("hits.jsonl");
+        let items = vec!["dormitory", "dirty room", "astronomer", "moon starer",
+                         "a gentleman", "elegant man"];
+        let mut adapter = JsonLinesAdapter::create(&path).unwrap()
+            .max_bytes(400);
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        process_iter(items, None, &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.hits(), 3);
+
+        // each record is too large to share a file
+        let rotated = |n: usize| {
+            let mut name = OsString::from(path.as_os_str());
+            name.push(format!(".{}", n));
+            read_records(Path::new(&name))
+        };
+        let mut records = rotated(1);
+        assert_eq!(records.len(), 1);
+        records.extend(rotated(2));
+        records.extend(read_records(&path));
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].p1, "dirty room");
+        assert_eq!(records[0].p2, "dormitory");
+        assert_eq!(records[0].fingerprint, "dimoorrty");
+        assert!(records[0].score.is_some());
+        assert_eq!(records[0].offset, None);
+
+        // reopening appends
+        let mut adapter = JsonLinesAdapter::create(&path).unwrap()
+            .include_scores(false);
+        let mut store = MemoryStore::new();
+        process_iter(vec!["dormitory", "dirty room"], None, &mut store, &mut adapter, &mut tester);
+        let records = read_records(&path);
+        assert_eq!(records.last().unwrap().score, None);
+
+        // near anagrams are written too
+        process_item("dirty rooms", &mut store, &mut adapter, &mut tester);
+        process_item_near("Dormitory!", &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.hits(), 2);
+        let record = read_records(&path).pop().unwrap();
+        assert_eq!(record.p1, "Dormitory!");
+        assert_eq!(record.p2, "dirty rooms");
+        assert_eq!(record.offset, Some(LetterOffset::Added('s')));
+    }
+}
@@ -2,13 +2,17 @@ use std::fmt;
 use std::hash::Hash;
 use std::collections::HashMap;
 use std::clone::Clone;
+use std::time::{Duration, Instant};
 
 use gnip_twitter_stream::{Tweet, MinimalTweet};
 
 use filters::is_ascii_letter;
-pub use edit_dist::EditDistance;
+use fold::fold_latin;
+use script::{Script, ScriptFingerprint};
+pub use edit_dist::{EditDistance, DistanceMetric};
 
 const ASCII_LOWERCASE_OFFSET: u8 = 97;
+const DEFAULT_BUCKET_SIZE: usize = 4;
 
 /// A trait for types that have some string representation suitable
 /// for anagram comparisons.
@@ -17,17 +21,63 @@ pub trait AsStr {
 }
 
 /// A trait for types which store anagram candidates.
+///
+/// Each key maps to a bucket of candidates, bounded by some `BucketPolicy`.
 pub trait Store<K, V> {
+    /// Removes all candidates for `key`.
     fn remove(&mut self, key: &K);
-    fn get_item(&self, key: &K) -> Option<V>;
+    /// Returns the candidates for `key`, oldest first.
+    fn get_items(&self, key: &K) -> Vec<V>;
+    /// Removes the candidate at `idx` in the bucket for `key`.
+    fn remove_item(&mut self, key: &K, idx: usize);
+    /// Adds a candidate for `key`, evicting an existing candidate if the
+    /// bucket is full.
     fn insert(&mut self, key: K, value: V);
 }
 
+/// A store whose operations can fail, such as one backed by files on disk.
+///
+/// The methods mirror those of `Store`.
+pub trait TryStore<K, V> {
+    type Error;
+    fn try_remove(&mut self, key: &K) -> Result<(), Self::Error>;
+    fn try_get_items(&self, key: &K) -> Result<Vec<V>, Self::Error>;
+    fn try_remove_item(&mut self, key: &K, idx: usize) -> Result<(), Self::Error>;
+    fn try_insert(&mut self, key: K, value: V) -> Result<(), Self::Error>;
+}
+
+/// Which candidate to drop when adding to a full bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    /// Drop the oldest candidate to make room for the new one.
+    Oldest,
+    /// Keep the existing candidates, and drop the new one.
+    Newest,
+}
+
+/// Determines how many candidates are kept per fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketPolicy {
+    pub size: usize,
+    pub eviction: Eviction,
+}
+
 /// A trait for types which handle results of anagram search.
 pub trait Adapter<T, TE: Tester<T>> {
     fn will_check(&mut self, _item: &T) { }
+    /// Called with the fingerprint of each item, before it is checked.
+    fn fingerprinted(&mut self, _item: &T, _hash: &TE::Fingerprint) { }
     fn possible_match(&mut self, _p1: &T, _p2: &T) { }
-    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint);
+    /// Called when the tester rejects a possible match.
+    fn rejected(&mut self, _p1: &T, _p2: &T, _reason: Rejection) { }
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, score: &MatchScore);
+    /// Called when `p2` is a near anagram of `p1`; `hash` is the fingerprint
+    /// of `p1`, and `offset` is how `p2` differs from it.
+    fn handle_near_match(&mut self, _p1: &T, _p2: &T, _hash: &TE::Fingerprint,
+                         _offset: LetterOffset, _score: &MatchScore) { }
+    /// Called when the letters of `p2` are a strict subset of the letters
+    /// of `p1`; `remainder` is the letters of `p1` that are left over.
+    fn handle_subset_match(&mut self, _p1: &T, _p2: &T, _remainder: &TE::Fingerprint) { }
 }
 
 /// A trait for types which validate potential anagrams.
@@ -39,21 +89,160 @@ pub trait Tester<T> {
     type Fingerprint: Hash + Eq;
     fn fingerprint(&mut self, s: &T) -> Self::Fingerprint;
     fn is_match(&mut self, p1: &T, p2: &T) -> bool;
+    /// Like `is_match`, but reports why a pair was rejected.
+    fn check(&mut self, p1: &T, p2: &T) -> Result<(), Rejection> {
+        if self.is_match(p1, p2) { Ok(()) } else { Err(Rejection::Other) }
+    }
+    /// Measures how two items differ. This is only called for pairs
+    /// accepted by `is_match`.
+    ///
+    /// The default implementation returns an empty score.
+    fn score(&mut self, _p1: &T, _p2: &T) -> MatchScore {
+        MatchScore::default()
+    }
+}
+
+/// A fingerprint which can be stored as bytes.
+pub trait Fingerprint: AsRef<[u8]> {
+    /// The number of letters counted by the fingerprint.
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The reasons a `Tester` may reject a pair of items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    /// The items are the same text.
+    Identical,
+    /// The items have the same letters in the same order.
+    SameLetters,
+    /// The items have too few letters.
+    TooShort,
+    /// The items' letters are too close, by edit distance.
+    TooSimilar,
+    /// The items have too many words in common.
+    SharedWords,
+    /// The items are mostly the same words, reordered.
+    Reordered,
+    /// The items are in different scripts.
+    DifferentScript,
+    /// The tester doesn't say.
+    Other,
+}
+
+/// A tester whose fingerprints can be offset by a single letter, for
+/// finding near anagrams.
+pub trait NearTester<T>: Tester<T> {
+    /// Returns each fingerprint with one letter more or one letter less
+    /// than `fingerprint`.
+    fn neighbours(&mut self, fingerprint: &Self::Fingerprint)
+        -> Vec<(Self::Fingerprint, LetterOffset)>;
+}
+
+/// How a near anagram differs from an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LetterOffset {
+    /// The near anagram has one more of this letter.
+    Added(char),
+    /// The near anagram has one fewer of this letter.
+    Removed(char),
+}
+
+/// A breakdown of how two matching items differ.
+///
+/// Distances are in chars (or the equivalent for the tester's metric);
+/// all features are computed on the normalized, letters-only form
+/// of each item.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct MatchScore {
+    /// The edit distance between the letters of each item.
+    pub char_distance: usize,
+    /// The edit distance between the (sorted) words of each item.
+    pub word_distance: usize,
+    /// The fraction of words that appear in both items.
+    pub shared_words: f64,
+    /// The number of letters in each item.
+    pub letter_count: usize,
+    /// The difference in the number of words in each item.
+    pub word_count_diff: usize,
 }
 
 pub struct SimpleAdapter<T> {
     hits: Vec<(T, T)>,
+    near_hits: Vec<(T, T, LetterOffset)>,
+    subset_hits: Vec<(T, T)>,
     seen: usize,
     tested: usize,
 }
 
 /// A (hashmap backed) in memory store.
-pub struct MemoryStore<K, V>(HashMap<K, V>);
+pub struct MemoryStore<K, V> {
+    items: HashMap<K, Vec<V>>,
+    policy: BucketPolicy,
+}
 
 /// A simple tester for ascii text.
 #[derive(Debug, Clone, Default)]
 pub struct AsciiTester {
     edit_dist: EditDistance,
+    config: AsciiTesterConfig,
+}
+
+/// Tuning parameters for `AsciiTester` (and the testers built on it).
+///
+/// All fields are optional when deserializing; missing fields take
+/// their default values.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AsciiTesterConfig {
+    /// Pairs whose letters differ by less than this fraction of their
+    /// length are rejected as too similar.
+    pub char_threshold: f64,
+    /// Pairs whose words (sorted) must differ by more than this fraction
+    /// of their length to be accepted.
+    pub word_threshold: f64,
+    /// Pairs with fewer letters than this are rejected.
+    pub min_letters: usize,
+    /// Pairs where more than this fraction of letters are in shared
+    /// words are rejected. Words also match adjacent words joined
+    /// together, so "lowkey" is shared with "low key".
+    pub max_shared_words: f64,
+    /// Which item's length the distances are divided by. Defaults to the
+    /// second item, which in `process_item` is the stored candidate.
+    pub normalize_by: NormalizeBy,
+    pub metric: DistanceMetric,
+}
+
+/// Which of a pair of items to use when normalizing distances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizeBy {
+    /// The second item of the pair, including any chars that aren't letters.
+    Second,
+    Shorter,
+    Longer,
+}
+
+/// A tester for latin-script text.
+///
+/// Diacritics are stripped and ligatures expanded (see `fold_latin`)
+/// before fingerprinting and comparison, so that "café" and "face" are
+/// both scored on the letters 'acef'.
+#[derive(Debug, Clone, Default)]
+pub struct LatinTester {
+    ascii: AsciiTester,
+}
+
+/// A tester for text in any supported script.
+///
+/// Each item is fingerprinted using the alphabet of its detected `Script`;
+/// items in different scripts are never matched.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptTester {
+    ascii: AsciiTester,
 }
 
 /// Stores a count for each ascii char, in order.
@@ -72,28 +261,82 @@ impl<T, TE> Adapter<T, TE> for SimpleAdapter<T>
         self.tested += 1;
     }
 
-    fn handle_match(&mut self, p1: &T, p2: &T, _hash: &TE::Fingerprint) {
+    fn handle_match(&mut self, p1: &T, p2: &T, _hash: &TE::Fingerprint, _score: &MatchScore) {
         self.hits.push((p1.to_owned(), p2.to_owned()));
     }
+
+    fn handle_near_match(&mut self, p1: &T, p2: &T, _hash: &TE::Fingerprint,
+                         offset: LetterOffset, _score: &MatchScore) {
+        self.near_hits.push((p1.to_owned(), p2.to_owned(), offset));
+    }
+
+    fn handle_subset_match(&mut self, p1: &T, p2: &T, _remainder: &TE::Fingerprint) {
+        self.subset_hits.push((p1.to_owned(), p2.to_owned()));
+    }
+}
+
+impl BucketPolicy {
+    pub fn new(size: usize, eviction: Eviction) -> Self {
+        assert!(size > 0, "bucket size must be non-zero");
+        BucketPolicy { size, eviction }
+    }
+
+    /// Adds `value` to `bucket`, evicting a candidate if necessary.
+    pub fn add<V>(&self, bucket: &mut Vec<V>, value: V) {
+        if bucket.len() >= self.size {
+            match self.eviction {
+                Eviction::Newest => return,
+                Eviction::Oldest => {
+                    let excess = bucket.len() + 1 - self.size;
+                    bucket.drain(..excess);
+                }
+            }
+        }
+        bucket.push(value);
+    }
+}
+
+impl Default for BucketPolicy {
+    fn default() -> Self {
+        BucketPolicy::new(DEFAULT_BUCKET_SIZE, Eviction::Oldest)
+    }
 }
 
 impl<K: Hash + Eq, V> MemoryStore<K, V> {
     pub fn new() -> Self {
-        MemoryStore(HashMap::new())
+        MemoryStore::with_policy(BucketPolicy::default())
+    }
+
+    pub fn with_policy(policy: BucketPolicy) -> Self {
+        MemoryStore { items: HashMap::new(), policy }
     }
 }
 
 impl<K: Hash + Eq, V: Clone> Store<K, V> for MemoryStore<K, V> {
     fn remove(&mut self, key: &K) {
-        self.0.remove(key);
+        self.items.remove(key);
     }
 
-    fn get_item(&self, key: &K) -> Option<V> {
-        self.0.get(key).map(V::clone)
+    fn get_items(&self, key: &K) -> Vec<V> {
+        self.items.get(key).cloned().unwrap_or_default()
+    }
+
+    fn remove_item(&mut self, key: &K, idx: usize) {
+        let is_empty = match self.items.get_mut(key) {
+            Some(bucket) if idx < bucket.len() => {
+                bucket.remove(idx);
+                bucket.is_empty()
+            }
+            _ => return,
+        };
+        if is_empty {
+            self.items.remove(key);
+        }
     }
 
     fn insert(&mut self, key: K, value: V) {
-        self.0.insert(key, value);
+        let policy = self.policy;
+        policy.add(self.items.entry(key).or_default(), value);
     }
 }
 
@@ -101,38 +344,218 @@ impl<T: AsStr> Tester<T> for AsciiTester {
     type Fingerprint = AsciiFingerprint;
 
     fn fingerprint(&mut self, s: &T) -> Self::Fingerprint {
-        let mut h: [u8; 26] = [0; 26];
-        for c in s.as_str().chars()
-            .filter(is_ascii_letter)
-            .flat_map(char::to_lowercase) {
-                let idx = (c as u8 - ASCII_LOWERCASE_OFFSET) as usize;
-                h[idx] += 1;
-        }
-        AsciiFingerprint(h)
+        AsciiFingerprint::new(s.as_str())
     }
 
     fn is_match(&mut self, p1: &T, p2: &T) -> bool {
         self.test_distance(p1.as_str(), p2.as_str())
     }
+
+    fn check(&mut self, p1: &T, p2: &T) -> Result<(), Rejection> {
+        self.check_letters(p1.as_str(), p2.as_str(), is_ascii_letter)
+    }
+
+    fn score(&mut self, p1: &T, p2: &T) -> MatchScore {
+        self.score_letters(p1.as_str(), p2.as_str(), is_ascii_letter)
+    }
+}
+
+impl<T: AsStr> NearTester<T> for AsciiTester {
+    fn neighbours(&mut self, fingerprint: &AsciiFingerprint)
+        -> Vec<(AsciiFingerprint, LetterOffset)>
+    {
+        fingerprint.neighbours()
+    }
+}
+
+impl<T: AsStr> Tester<T> for LatinTester {
+    type Fingerprint = AsciiFingerprint;
+
+    fn fingerprint(&mut self, s: &T) -> Self::Fingerprint {
+        self.ascii.fingerprint(&fold_latin(s.as_str()))
+    }
+
+    fn is_match(&mut self, p1: &T, p2: &T) -> bool {
+        self.check(p1, p2).is_ok()
+    }
+
+    fn check(&mut self, p1: &T, p2: &T) -> Result<(), Rejection> {
+        let a = fold_latin(p1.as_str());
+        let b = fold_latin(p2.as_str());
+        self.ascii.check_letters(&a, &b, is_ascii_letter)
+    }
+
+    fn score(&mut self, p1: &T, p2: &T) -> MatchScore {
+        let a = fold_latin(p1.as_str());
+        let b = fold_latin(p2.as_str());
+        self.ascii.score_letters(&a, &b, is_ascii_letter)
+    }
+}
+
+impl<T: AsStr> NearTester<T> for LatinTester {
+    fn neighbours(&mut self, fingerprint: &AsciiFingerprint)
+        -> Vec<(AsciiFingerprint, LetterOffset)>
+    {
+        fingerprint.neighbours()
+    }
+}
+
+impl<T: AsStr> Tester<T> for ScriptTester {
+    type Fingerprint = ScriptFingerprint;
+
+    fn fingerprint(&mut self, s: &T) -> Self::Fingerprint {
+        ScriptFingerprint::new(s.as_str())
+    }
+
+    fn is_match(&mut self, p1: &T, p2: &T) -> bool {
+        self.check(p1, p2).is_ok()
+    }
+
+    fn check(&mut self, p1: &T, p2: &T) -> Result<(), Rejection> {
+        let script = match Script::detect(p1.as_str()) {
+            Some(script) => script,
+            None => return Err(Rejection::DifferentScript),
+        };
+        if Script::detect(p2.as_str()) != Some(script) {
+            return Err(Rejection::DifferentScript)
+        }
+        let a = script.normalize(p1.as_str());
+        let b = script.normalize(p2.as_str());
+        self.ascii.check_letters(&a, &b, |c| script.is_letter(c))
+    }
+
+    fn score(&mut self, p1: &T, p2: &T) -> MatchScore {
+        let script = Script::detect(p1.as_str()).unwrap_or(Script::Latin);
+        let a = script.normalize(p1.as_str());
+        let b = script.normalize(p2.as_str());
+        self.ascii.score_letters(&a, &b, |c| script.is_letter(c))
+    }
+}
+
+impl Default for AsciiTesterConfig {
+    fn default() -> Self {
+        AsciiTesterConfig {
+            char_threshold: 0.5,
+            word_threshold: 0.5,
+            min_letters: 0,
+            max_shared_words: 0.5,
+            normalize_by: NormalizeBy::Second,
+            metric: DistanceMetric::Levenshtein,
+        }
+    }
+}
+
+impl NormalizeBy {
+    fn len(self, a: &str, b: &str) -> usize {
+        let a_len = a.chars().count();
+        let b_len = b.chars().count();
+        match self {
+            NormalizeBy::Second => b_len,
+            NormalizeBy::Shorter => a_len.min(b_len),
+            NormalizeBy::Longer => a_len.max(b_len),
+        }
+    }
+}
+
+impl LatinTester {
+    pub fn new(config: AsciiTesterConfig) -> Self {
+        LatinTester { ascii: AsciiTester::new(config) }
+    }
+}
+
+impl ScriptTester {
+    pub fn new(config: AsciiTesterConfig) -> Self {
+        ScriptTester { ascii: AsciiTester::new(config) }
+    }
 }
 
 impl AsciiTester {
+    pub fn new(config: AsciiTesterConfig) -> Self {
+        AsciiTester { edit_dist: EditDistance::default(), config }
+    }
+
     fn test_distance(&mut self, a: &str, b: &str) -> bool {
-        const MIN_DIST: f64 = 0.5;
-        if a == b { return false }
-        let a1 = lowercase_filtered(a);
-        let b1 = lowercase_filtered(b);
-        if a1 == b1 { return false }
-        let dist = self.edit_dist.distance(&a1, &b1);
-        if (dist as f64) / (b.chars().count() as f64) < MIN_DIST {
-            return false
+        self.check_letters(a, b, is_ascii_letter).is_ok()
+    }
+
+    /// Checks that two strings are sufficiently different, considering
+    /// only the chars for which `is_letter` returns `true`.
+    fn check_letters<F>(&mut self, a: &str, b: &str, is_letter: F) -> Result<(), Rejection>
+        where F: Fn(&char) -> bool,
+    {
+        let config = &self.config;
+        if a == b { return Err(Rejection::Identical) }
+        let a1 = lowercase_filtered(a, &is_letter);
+        let b1 = lowercase_filtered(b, &is_letter);
+        if a1 == b1 { return Err(Rejection::SameLetters) }
+        if a1.chars().count() < config.min_letters { return Err(Rejection::TooShort) }
+        // reject if dist / (len * max_cost) < char_threshold
+        let max_cost = config.metric.max_cost() as f64;
+        let len = config.normalize_by.len(a, b) as f64 * max_cost;
+        let max_similar = (config.char_threshold * len).ceil() as usize;
+        let max_similar = max_similar.saturating_sub(1);
+        if self.edit_dist.metric_within(&config.metric, &a1, &b1, max_similar).is_some() {
+            return Err(Rejection::TooSimilar)
         }
 
-        let a = word_split_sort(a);
-        let b = word_split_sort(b);
+        if config.max_shared_words < 1.0 {
+            let a_words = split_words(a, &is_letter);
+            let b_words = split_words(b, &is_letter);
+            if shared_word_ratio(&a_words, &b_words) > config.max_shared_words {
+                return Err(Rejection::SharedWords)
+            }
+        }
+
+        let a = word_split_sort(a, &is_letter);
+        let b = word_split_sort(b, &is_letter);
         //assert_eq!(a.len(), b.len(), "{} / {}", a, b);
-        let dist = self.edit_dist.distance(&a, &b);
-        (dist as f64) / (b.chars().count() as f64) > MIN_DIST
+        // accept if dist / (len * max_cost) > word_threshold
+        let len = config.normalize_by.len(&a, &b) as f64 * max_cost;
+        let max_similar = (config.word_threshold * len).floor() as usize;
+        match self.edit_dist.metric_within(&config.metric, &a, &b, max_similar) {
+            Some(_) => Err(Rejection::Reordered),
+            None => Ok(()),
+        }
+    }
+
+    /// Computes the `MatchScore` for two strings, considering only the
+    /// chars for which `is_letter` returns `true`.
+    fn score_letters<F>(&mut self, a: &str, b: &str, is_letter: F) -> MatchScore
+        where F: Fn(&char) -> bool,
+    {
+        let metric = self.config.metric;
+        let a1 = lowercase_filtered(a, &is_letter);
+        let b1 = lowercase_filtered(b, &is_letter);
+        let char_distance = self.edit_dist.metric_distance(&metric, &a1, &b1);
+
+        let word_distance = self.edit_dist.metric_distance(&metric,
+                                                          word_split_sort(a, &is_letter),
+                                                          word_split_sort(b, &is_letter));
+        let a_words = split_words(a, &is_letter);
+        let b_words = split_words(b, &is_letter);
+        let shared_words = shared_word_ratio(&a_words, &b_words);
+        let max_words = a_words.len().max(b_words.len());
+
+        MatchScore {
+            char_distance,
+            word_distance,
+            shared_words,
+            letter_count: a1.chars().count(),
+            word_count_diff: max_words - a_words.len().min(b_words.len()),
+        }
+    }
+}
+
+impl MatchScore {
+    /// A single value for ranking hits, between 0 and 1; higher is better.
+    ///
+    /// This is the average of the char and word distances, relative to
+    /// the letter count, discounted by the fraction of shared words.
+    pub fn rank(&self) -> f64 {
+        if self.letter_count == 0 { return 0.0 }
+        let dist = (self.char_distance + self.word_distance) as f64
+            / (2 * self.letter_count) as f64;
+        dist.min(1.0) * (1.0 - self.shared_words)
     }
 }
 
@@ -151,29 +574,122 @@ impl fmt::Display for AsciiFingerprint {
     }
 }
 
+impl AsciiFingerprint {
+    /// Counts the ascii letters in `s`, ignoring case.
+    pub fn new(s: &str) -> Self {
+        let mut h: [u8; 26] = [0; 26];
+        for c in s.chars()
+            .filter(is_ascii_letter)
+            .flat_map(char::to_lowercase) {
+                let idx = (c as u8 - ASCII_LOWERCASE_OFFSET) as usize;
+                h[idx] += 1;
+        }
+        AsciiFingerprint(h)
+    }
+
+    /// Recreates a fingerprint from the bytes returned by `as_ref`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 26 { return None }
+        let mut h: [u8; 26] = [0; 26];
+        h.copy_from_slice(bytes);
+        Some(AsciiFingerprint(h))
+    }
+
+    /// The total number of letters.
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|c| *c as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|c| *c == 0)
+    }
+
+    /// Returns `true` if this fingerprint has at least as many of each
+    /// letter as `other`.
+    pub fn contains(&self, other: &AsciiFingerprint) -> bool {
+        self.0.iter().zip(other.0.iter()).all(|(a, b)| a >= b)
+    }
+
+    /// Returns the letters left after removing `other`, if this fingerprint
+    /// contains it.
+    pub fn subtract(&self, other: &AsciiFingerprint) -> Option<AsciiFingerprint> {
+        if !self.contains(other) { return None }
+        let mut result = self.0;
+        for (a, b) in result.iter_mut().zip(other.0.iter()) {
+            *a -= b;
+        }
+        Some(AsciiFingerprint(result))
+    }
+
+    /// Returns each fingerprint with one letter more or one letter less
+    /// than this one.
+    pub fn neighbours(&self) -> Vec<(AsciiFingerprint, LetterOffset)> {
+        let mut result = Vec::new();
+        for (idx, count) in self.0.iter().enumerate() {
+            let chr = (idx as u8 + ASCII_LOWERCASE_OFFSET) as char;
+            if *count < u8::max_value() {
+                let mut added = self.0;
+                added[idx] += 1;
+                result.push((AsciiFingerprint(added), LetterOffset::Added(chr)));
+            }
+            if *count > 0 {
+                let mut removed = self.0;
+                removed[idx] -= 1;
+                result.push((AsciiFingerprint(removed), LetterOffset::Removed(chr)));
+            }
+        }
+        result
+    }
+}
+
 impl AsRef<[u8]> for AsciiFingerprint {
     fn as_ref(&self) -> &[u8] {
         &self.0
     }
 }
 
+impl Fingerprint for AsciiFingerprint {
+    fn len(&self) -> usize {
+        AsciiFingerprint::len(self)
+    }
+}
+
 
 impl<T: AsStr> SimpleAdapter<T> {
     pub fn new() -> Self {
         SimpleAdapter {
             hits: Vec::new(),
+            near_hits: Vec::new(),
+            subset_hits: Vec::new(),
             seen: 0,
             tested: 0,
         }
     }
 
+    /// Pairs of anagrams found so far.
+    pub fn hits(&self) -> &[(T, T)] {
+        &self.hits
+    }
+
+    /// Pairs where the letters of the second item are a subset of the first.
+    pub fn subset_hits(&self) -> &[(T, T)] {
+        &self.subset_hits
+    }
+
     pub fn print_results(&self) {
         for &(ref one, ref two) in self.hits.iter() {
             println!("---------\n{}\n--↕︎--\n{}",
                      one.as_str(),
                      two.as_str());
         }
-        println!("saw {} items, found {} anagrams.", self.seen, self.hits.len());
+        for &(ref one, ref two, offset) in self.near_hits.iter() {
+            println!("---------\n{}\n--{:?}--\n{}",
+                     one.as_str(),
+                     offset,
+                     two.as_str());
+        }
+        println!("saw {} items, found {} anagrams, {} near anagrams and {} subsets.",
+                 self.seen, self.hits.len(), self.near_hits.len(), self.subset_hits.len());
     }
 }
 
@@ -187,7 +703,171 @@ pub fn process_item<T, S, A, TE>(item: T,
           A: Adapter<T, TE>,
           TE: Tester<T>,
 {
-    process_item_impl(item, store, adapter, tester, true)
+    let ident = tester.fingerprint(&item);
+    process_item_impl(item, ident, store, adapter, tester, true)
+}
+
+/// Handles a single item, also checking it against stored items whose
+/// fingerprints differ by a single letter.
+///
+/// Near anagrams are reported with `Adapter::handle_near_match`, and are
+/// only checked if there is no exact match. Unlike exact matches, neither
+/// item is removed from the store.
+pub fn process_item_near<T, S, A, TE>(item: T,
+                                      store: &mut S,
+                                      adapter: &mut A,
+                                      tester: &mut TE)
+    where T: AsStr,
+          S: Store<TE::Fingerprint, T>,
+          A: Adapter<T, TE>,
+          TE: NearTester<T>,
+{
+    let ident = tester.fingerprint(&item);
+    adapter.will_check(&item);
+    adapter.fingerprinted(&item, &ident);
+    if find_match(&item, &ident, store, adapter, tester) { return }
+
+    'outer: for (neighbour, offset) in tester.neighbours(&ident) {
+        for candidate in store.get_items(&neighbour).iter() {
+            adapter.possible_match(&item, candidate);
+            match tester.check(&item, candidate) {
+                Ok(()) => {
+                    let score = tester.score(&item, candidate);
+                    adapter.handle_near_match(&item, candidate, &ident, offset, &score);
+                    break 'outer;
+                }
+                Err(reason) => adapter.rejected(&item, candidate, reason),
+            }
+        }
+    }
+    store.insert(ident, item)
+}
+
+/// A summary of a batch of items handled by `process_iter` or `check_iter`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchSummary {
+    /// Items read from the input.
+    pub seen: usize,
+    /// Items rejected by the filter.
+    pub filtered: usize,
+    /// Items without a match that were passed to the store. Depending on
+    /// its `BucketPolicy`, the store may have discarded some of them.
+    pub offered: usize,
+    /// Pairs of items with the same fingerprint that were tested.
+    pub possible_matches: usize,
+    pub hits: usize,
+    pub elapsed: Duration,
+}
+
+/// Handles each item in `items`, skipping any for which `filter` returns
+/// `false`.
+pub fn process_iter<I, T, S, A, TE>(items: I,
+                                    filter: Option<&mut FnMut(&T) -> bool>,
+                                    store: &mut S,
+                                    adapter: &mut A,
+                                    tester: &mut TE) -> BatchSummary
+    where I: IntoIterator<Item=T>,
+          T: AsStr,
+          S: Store<TE::Fingerprint, T>,
+          A: Adapter<T, TE>,
+          TE: Tester<T>,
+{
+    process_iter_impl(items, filter, store, adapter, tester, true)
+}
+
+/// Checks each item in `items` against stored items, without modifying
+/// storage, skipping any for which `filter` returns `false`.
+pub fn check_iter<I, T, S, A, TE>(items: I,
+                                  filter: Option<&mut FnMut(&T) -> bool>,
+                                  store: &mut S,
+                                  adapter: &mut A,
+                                  tester: &mut TE) -> BatchSummary
+    where I: IntoIterator<Item=T>,
+          T: AsStr,
+          S: Store<TE::Fingerprint, T>,
+          A: Adapter<T, TE>,
+          TE: Tester<T>,
+{
+    process_iter_impl(items, filter, store, adapter, tester, false)
+}
+
+fn process_iter_impl<I, T, S, A, TE>(items: I,
+                                     mut filter: Option<&mut FnMut(&T) -> bool>,
+                                     store: &mut S,
+                                     adapter: &mut A,
+                                     tester: &mut TE,
+                                     store_new: bool) -> BatchSummary
+    where I: IntoIterator<Item=T>,
+          T: AsStr,
+          S: Store<TE::Fingerprint, T>,
+          A: Adapter<T, TE>,
+          TE: Tester<T>,
+{
+    let start = Instant::now();
+    let mut summary = BatchSummary::default();
+    let mut adapter = CountingAdapter { inner: adapter, possible_matches: 0, hits: 0 };
+    for item in items {
+        summary.seen += 1;
+        if let Some(ref mut filter) = filter {
+            if !filter(&item) {
+                summary.filtered += 1;
+                continue
+            }
+        }
+        let hits = adapter.hits;
+        let ident = tester.fingerprint(&item);
+        process_item_impl(item, ident, store, &mut adapter, tester, store_new);
+        if store_new && adapter.hits == hits {
+            summary.offered += 1;
+        }
+    }
+    summary.possible_matches = adapter.possible_matches;
+    summary.hits = adapter.hits;
+    summary.elapsed = start.elapsed();
+    summary
+}
+
+/// Forwards to another adapter, counting possible matches and hits.
+struct CountingAdapter<'a, A: 'a> {
+    inner: &'a mut A,
+    possible_matches: usize,
+    hits: usize,
+}
+
+impl<'a, T, TE, A> Adapter<T, TE> for CountingAdapter<'a, A>
+    where TE: Tester<T>,
+          A: Adapter<T, TE>,
+{
+    fn will_check(&mut self, item: &T) {
+        self.inner.will_check(item)
+    }
+
+    fn fingerprinted(&mut self, item: &T, hash: &TE::Fingerprint) {
+        self.inner.fingerprinted(item, hash)
+    }
+
+    fn possible_match(&mut self, p1: &T, p2: &T) {
+        self.possible_matches += 1;
+        self.inner.possible_match(p1, p2)
+    }
+
+    fn rejected(&mut self, p1: &T, p2: &T, reason: Rejection) {
+        self.inner.rejected(p1, p2, reason)
+    }
+
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, score: &MatchScore) {
+        self.hits += 1;
+        self.inner.handle_match(p1, p2, hash, score)
+    }
+
+    fn handle_near_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint,
+                         offset: LetterOffset, score: &MatchScore) {
+        self.inner.handle_near_match(p1, p2, hash, offset, score)
+    }
+
+    fn handle_subset_match(&mut self, p1: &T, p2: &T, remainder: &TE::Fingerprint) {
+        self.inner.handle_subset_match(p1, p2, remainder)
+    }
 }
 
 /// Checks a new item against stored items, without modifying storage.
@@ -201,66 +881,145 @@ pub fn check_item<T, S, A, TE>(item: T,
           A: Adapter<T, TE>,
           TE: Tester<T>,
 {
-    process_item_impl(item, store, adapter, tester, false)
+    let ident = tester.fingerprint(&item);
+    process_item_impl(item, ident, store, adapter, tester, false)
 }
 
-fn process_item_impl<T, S, A, TE>(item: T,
-                                  store: &mut S,
-                                  adapter: &mut A,
-                                  tester: &mut TE,
-                                  store_new: bool)
+pub(super) fn process_item_impl<T, S, A, TE>(item: T,
+                                             ident: TE::Fingerprint,
+                                             store: &mut S,
+                                             adapter: &mut A,
+                                             tester: &mut TE,
+                                             store_new: bool)
     where T: AsStr,
           S: Store<TE::Fingerprint, T>,
           A: Adapter<T, TE>,
           TE: Tester<T>,
 {
-    let ident = tester.fingerprint(&item);
     adapter.will_check(&item);
-    {
-        let hit = store.get_item(&ident);
-        let is_hit = match store.get_item(&ident) {
-            Some(ref hit) => {
-                adapter.possible_match(&item, hit);
-                tester.is_match(&item, hit)
-            }
-            None => false,
-        };
-
-        if is_hit {
-            store.remove(&ident);
-            return adapter.handle_match(&item, &hit.unwrap(), &ident);
-        }
-    }
+    adapter.fingerprinted(&item, &ident);
+    if find_match(&item, &ident, store, adapter, tester) { return }
 
     if store_new {
         store.insert(ident, item)
     }
 }
 
-fn lowercase_filtered<T: AsRef<str>>(s: T) -> String {
+/// Checks `item` against the stored items with the same fingerprint,
+/// removing and reporting the first match. Returns `true` on a match.
+fn find_match<T, S, A, TE>(item: &T,
+                           ident: &TE::Fingerprint,
+                           store: &mut S,
+                           adapter: &mut A,
+                           tester: &mut TE) -> bool
+    where T: AsStr,
+          S: Store<TE::Fingerprint, T>,
+          A: Adapter<T, TE>,
+          TE: Tester<T>,
+{
+    for (idx, candidate) in store.get_items(ident).iter().enumerate() {
+        adapter.possible_match(item, candidate);
+        match tester.check(item, candidate) {
+            Ok(()) => {
+                store.remove_item(ident, idx);
+                let score = tester.score(item, candidate);
+                adapter.handle_match(item, candidate, ident, &score);
+                return true
+            }
+            Err(reason) => adapter.rejected(item, candidate, reason),
+        }
+    }
+    false
+}
+
+fn lowercase_filtered<T, F>(s: T, is_letter: F) -> String
+    where T: AsRef<str>,
+          F: Fn(&char) -> bool,
+{
     s.as_ref().chars()
-        .filter(is_ascii_letter)
+        .filter(is_letter)
         .flat_map(char::to_lowercase)
         .collect::<String>()
 }
 
 //TODO: this has three allocations too many
 /// Given a string, removes ignored chars, lowercases, and sorts by word.
-fn word_split_sort<T: AsRef<str>>(s: T) -> String {
+fn word_split_sort<T, F>(s: T, is_letter: F) -> String
+    where T: AsRef<str>,
+          F: Fn(&char) -> bool,
+{
+    let mut words = split_words(s, is_letter);
+    words.sort();
+    words.as_slice().join(" ")
+}
+
+/// Returns the lowercased words in a string, in order.
+fn split_words<T, F>(s: T, is_letter: F) -> Vec<String>
+    where T: AsRef<str>,
+          F: Fn(&char) -> bool,
+{
     let s = s.as_ref();
     let s: String = s.chars()
-        .flat_map(|c| if is_ascii_letter(&c) { c.to_lowercase() } else { ' '.to_lowercase() })
+        .flat_map(|c| if is_letter(&c) { c.to_lowercase() } else { ' '.to_lowercase() })
         .collect();
-    let mut words = s.split_whitespace()
+    s.split_whitespace()
         .map(|s| {
             s.chars()
-                .filter(is_ascii_letter)
+                .filter(&is_letter)
                 .flat_map(char::to_lowercase)
                 .collect::<String>()
         })
-    .collect::<Vec<_>>();
-    words.sort();
-    words.as_slice().join(" ")
+    .collect::<Vec<_>>()
+}
+
+/// Returns the fraction of letters in two lists of words that belong to
+/// words found in both, where a word can also match two adjacent words
+/// from the other list joined together (in either order).
+fn shared_word_ratio(a: &[String], b: &[String]) -> f64 {
+    let letters = |words: &[String]| words.iter().map(|w| w.chars().count()).sum::<usize>();
+    let total = letters(a).max(letters(b));
+    if total == 0 { return 0.0 }
+
+    let mut a_used = vec![false; a.len()];
+    let mut b_used = vec![false; b.len()];
+    let mut shared = 0;
+    for (i, word) in a.iter().enumerate() {
+        if let Some(j) = (0..b.len()).find(|&j| !b_used[j] && b[j] == *word) {
+            a_used[i] = true;
+            b_used[j] = true;
+            shared += word.chars().count();
+        }
+    }
+    shared += match_joined(a, &mut a_used, b, &mut b_used);
+    shared += match_joined(b, &mut b_used, a, &mut a_used);
+    shared as f64 / total as f64
+}
+
+fn is_joined(word: &str, first: &str, second: &str) -> bool {
+    word.starts_with(first) && word.ends_with(second)
+}
+
+/// Matches unused words in `words` against unused adjacent pairs in
+/// `other`, returning the number of letters matched.
+fn match_joined(words: &[String], words_used: &mut [bool],
+                other: &[String], other_used: &mut [bool]) -> usize {
+    let mut shared = 0;
+    for (i, word) in words.iter().enumerate() {
+        if words_used[i] { continue }
+        let found = (1..other.len()).find(|&j| {
+            let (first, second) = (&other[j - 1], &other[j]);
+            !other_used[j - 1] && !other_used[j]
+                && word.len() == first.len() + second.len()
+                && (is_joined(word, first, second) || is_joined(word, second, first))
+        });
+        if let Some(j) = found {
+            words_used[i] = true;
+            other_used[j - 1] = true;
+            other_used[j] = true;
+            shared += word.chars().count();
+        }
+    }
+    shared
 }
 
 #[allow(dead_code)]
@@ -299,6 +1058,9 @@ impl AsStr for MinimalTweet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json;
+    use edit_dist::EditCosts;
+
     #[test]
     fn cleverness() {
         let inp = "aabbccddeefffffghiz";
@@ -312,7 +1074,7 @@ mod tests {
     fn word_split() {
         let one = "twenty six\n\n#MissUniverse #Philippines";
         let two = "#MissUniverse #Philippines\n\ntwenty six";
-        assert_eq!(word_split_sort(one), word_split_sort(two));
+        assert_eq!(word_split_sort(one, is_ascii_letter), word_split_sort(two, is_ascii_letter));
     }
 
     #[test]
@@ -326,12 +1088,252 @@ mod tests {
 
         let one = "joji // will he";
         let two = "willhe//joji";
-        eprintln!("{} / {}", word_split_sort(one), word_split_sort(two));
+        eprintln!("{} / {}", word_split_sort(one, is_ascii_letter), word_split_sort(two, is_ascii_letter));
 
         assert!(!tester.test_distance(one, two));
         assert!(!tester.is_match(&one, &two));
     }
 
+    #[test]
+    fn metrics() {
+        // four swapped pairs: 8 levenshtein edits, but only 4 transpositions
+        let one = "abxcdxefxghx";
+        let two = "baxdcxfexhgx";
+        let mut levenshtein = AsciiTester::default();
+        let mut damerau = AsciiTester::new(AsciiTesterConfig {
+            metric: DistanceMetric::Damerau,
+            ..Default::default()
+        });
+        assert!(levenshtein.test_distance(one, two));
+        assert!(!damerau.test_distance(one, two));
+
+        // doubling every cost doubles the distance, but not the result
+        let costs = EditCosts { insert: 2, delete: 2, substitute: 2, transpose: 4 };
+        let mut weighted = AsciiTester::new(AsciiTesterConfig {
+            metric: DistanceMetric::Weighted(costs),
+            ..Default::default()
+        });
+        for &(a, b) in [(one, two), ("dormitory", "dirty room"),
+                        ("the morse code", "here come dots"),
+                        ("I hate being sick", "I hate bieng sick")].iter() {
+            assert_eq!(weighted.test_distance(a, b), levenshtein.test_distance(a, b), "{} / {}", a, b);
+        }
+    }
+
+    #[test]
+    fn config() {
+        let config: AsciiTesterConfig = serde_json::from_str(r#"{
+            "char_threshold": 0.3,
+            "min_letters": 12,
+            "normalize_by": "longer",
+            "metric": {"weighted": {"insert": 1, "delete": 1, "substitute": 2, "transpose": 1}}
+        }"#).unwrap();
+        assert_eq!(config.word_threshold, 0.5);
+        assert_eq!(config.normalize_by, NormalizeBy::Longer);
+        assert_eq!(config.metric, DistanceMetric::Weighted(EditCosts {
+            insert: 1, delete: 1, substitute: 2, transpose: 1,
+        }));
+
+        let one = "dormitory";
+        let two = "dirty room";
+        assert!(AsciiTester::default().test_distance(one, two));
+        assert!(!AsciiTester::new(config).test_distance(one, two));
+
+        let config: AsciiTesterConfig = serde_json::from_str(r#"{"metric": "damerau"}"#).unwrap();
+        assert_eq!(config.metric, DistanceMetric::Damerau);
+    }
+
+    #[test]
+    fn normalization() {
+        let one = "Dormitory";
+        let two = "dirty room!!!!!!!!!!!";
+        let mut tester = AsciiTester::default();
+        // by default, the second item's length is used, punctuation and all
+        assert!(!tester.test_distance(one, two));
+        assert!(tester.test_distance(two, one));
+        let mut shorter = AsciiTester::new(AsciiTesterConfig {
+            normalize_by: NormalizeBy::Shorter,
+            ..Default::default()
+        });
+        assert!(shorter.test_distance(one, two));
+    }
+
+    #[test]
+    fn shared_words() {
+        let words = |s: &str| split_words(s, is_ascii_letter);
+        let ratio = |a: &str, b: &str| shared_word_ratio(&words(a), &words(b));
+        assert_eq!(ratio("lowkey maybe highkey", "Low key. Maybe high key."), 1.0);
+        assert_eq!(ratio("mine istomorrow", "tomorrow is mine"), 1.0);
+        assert_eq!(ratio("Go for it. Fuck.", "fUCK I forgot"), 4.0 / 11.0);
+        assert_eq!(ratio("dormitory", "dirty room"), 0.0);
+        // each word is only matched once
+        assert_eq!(ratio("no no no", "on no on"), 2.0 / 6.0);
+
+        let mut tester = AsciiTester::default();
+        assert!(!tester.test_distance("mine istomorrow", "tomorrow is mine"));
+        let mut tester = AsciiTester::new(AsciiTesterConfig {
+            max_shared_words: 1.0,
+            ..Default::default()
+        });
+        assert!(tester.test_distance("mine istomorrow", "tomorrow is mine"));
+    }
+
+    #[test]
+    fn rejections() {
+        let mut tester = AsciiTester::default();
+        let mut check = |a: &str, b: &str| tester.check(&a, &b);
+        assert_eq!(check("one two", "one two"), Err(Rejection::Identical));
+        assert_eq!(check("one two", "One two!"), Err(Rejection::SameLetters));
+        assert_eq!(check("I hate being sick", "I hate bieng sick"), Err(Rejection::TooSimilar));
+        assert_eq!(check("mine istomorrow", "tomorrow is mine"), Err(Rejection::SharedWords));
+        assert_eq!(check("dormitory", "dirty room"), Ok(()));
+
+        let mut tester = ScriptTester::default();
+        assert_eq!(tester.check(&"Пора домой", &"домой пора"), Err(Rejection::SharedWords));
+        assert_eq!(tester.check(&"Пора домой", &"hello"), Err(Rejection::DifferentScript));
+        let mut tester = ScriptTester::new(AsciiTesterConfig {
+            max_shared_words: 1.0,
+            ..Default::default()
+        });
+        assert_eq!(tester.check(&"Пора домой", &"домой пора"), Err(Rejection::Reordered));
+    }
+
+    #[test]
+    fn scores() {
+        let mut tester = AsciiTester::default();
+        let score = tester.score(&"Dormitory", &"dirty room!");
+        assert_eq!(score.letter_count, 9);
+        assert_eq!(score.word_count_diff, 1);
+        assert_eq!(score.shared_words, 0.0);
+        assert_eq!(score.char_distance, tester.edit_dist.distance("dormitory", "dirtyroom"));
+
+        let score = tester.score(&"the night is young", &"the thing is young");
+        assert_eq!(score.shared_words, 10.0 / 15.0);
+        assert_eq!(score.word_count_diff, 0);
+        assert_eq!(score.char_distance, 5);
+        assert_eq!(score.word_distance, 7);
+        // mostly shared words makes for a poor hit: (5 + 7) / 30 * (1 - 2/3)
+        assert!((score.rank() - 0.4 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn latin_folding() {
+        let mut ascii = AsciiTester::default();
+        let mut latin = LatinTester::default();
+        // ascii drops the accented letter entirely
+        assert_eq!(ascii.fingerprint(&"café"), ascii.fingerprint(&"fac"));
+        assert_ne!(ascii.fingerprint(&"café"), ascii.fingerprint(&"face"));
+        assert_eq!(latin.fingerprint(&"café"), latin.fingerprint(&"face"));
+        assert_eq!(latin.fingerprint(&"café").to_string(), "acef");
+        assert_eq!(latin.fingerprint(&"naïve"), latin.fingerprint(&"vanie"));
+        assert_eq!(latin.fingerprint(&"Straße").to_string(), "aerssst");
+
+        // folding alone shouldn't produce a match
+        assert!(!latin.is_match(&"Crème brûlée", &"creme brulee"));
+    }
+
+    #[test]
+    fn script_tester() {
+        let mut tester = ScriptTester::default();
+        let one = "Апельсин!";
+        let two = "спаниель";
+        assert_eq!(tester.fingerprint(&one), tester.fingerprint(&two));
+        assert!(tester.is_match(&one, &two));
+        assert!(!tester.is_match(&one, &"апельсин"));
+
+        let one = "Пора домой";
+        let two = "домой пора";
+        assert_eq!(tester.fingerprint(&one), tester.fingerprint(&two));
+        assert!(!tester.is_match(&one, &two));
+    }
+
+    #[test]
+    fn bucket_policy() {
+        let mut bucket = Vec::new();
+        let policy = BucketPolicy::new(2, Eviction::Oldest);
+        for i in 0..4 {
+            policy.add(&mut bucket, i);
+        }
+        assert_eq!(bucket, vec![2, 3]);
+
+        let mut bucket = Vec::new();
+        let policy = BucketPolicy::new(2, Eviction::Newest);
+        for i in 0..4 {
+            policy.add(&mut bucket, i);
+        }
+        assert_eq!(bucket, vec![0, 1]);
+    }
+
+    #[test]
+    fn earlier_candidates_kept() {
+        let mut adapter = SimpleAdapter::new();
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+
+        process_item("I’m so annoyed by him", &mut store, &mut adapter, &mut tester);
+        // same letters, but too similar to match
+        process_item("im so annoyed by him", &mut store, &mut adapter, &mut tester);
+        process_item("so annoyed by him im", &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.hits.len(), 0);
+
+        process_item("Hi my name is nobody", &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.hits.len(), 1);
+        assert_eq!(adapter.hits[0].1, "I’m so annoyed by him");
+        // the matched candidate is removed, the others remain
+        let ident = tester.fingerprint(&"Hi my name is nobody");
+        assert_eq!(store.get_items(&ident).len(), 2);
+    }
+
+    #[test]
+    fn near_anagrams() {
+        let fingerprint = AsciiTester::default().fingerprint(&"aab");
+        let neighbours = fingerprint.neighbours();
+        assert_eq!(neighbours.len(), 26 + 2);
+        assert!(neighbours.contains(&(AsciiTester::default().fingerprint(&"ab"),
+                                      LetterOffset::Removed('a'))));
+        assert!(neighbours.contains(&(AsciiTester::default().fingerprint(&"aabz"),
+                                      LetterOffset::Added('z'))));
+
+        let mut adapter = SimpleAdapter::new();
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        process_item("dirty rooms", &mut store, &mut adapter, &mut tester);
+        process_item("dormitory", &mut store, &mut adapter, &mut tester);
+        assert!(adapter.near_hits.is_empty());
+
+        process_item_near("Dormitory!", &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.near_hits, vec![
+            ("Dormitory!", "dirty rooms", LetterOffset::Added('s')),
+        ]);
+        // exact matches take precedence
+        process_item_near("dirty room", &mut store, &mut adapter, &mut tester);
+        assert_eq!(adapter.hits.len(), 1);
+        assert_eq!(adapter.near_hits.len(), 1);
+    }
+
+    #[test]
+    fn batches() {
+        let items = vec!["dormitory", "astronomer", "dirty room", "hi", "moon starer", "dirty room"];
+        let mut adapter = SimpleAdapter::new();
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        let summary = process_iter(items.clone(), Some(&mut |s: &&str| s.len() > 2),
+                                   &mut store, &mut adapter, &mut tester);
+        assert_eq!(summary.seen, 6);
+        assert_eq!(summary.filtered, 1);
+        assert_eq!(summary.hits, 2);
+        assert_eq!(summary.offered, 3);
+        assert_eq!(summary.possible_matches, 2);
+        assert_eq!(adapter.hits.len(), 2);
+
+        let summary = check_iter(items, None, &mut store, &mut adapter, &mut tester);
+        assert_eq!(summary.filtered, 0);
+        assert_eq!(summary.offered, 0);
+        assert_eq!(summary.hits, 1);
+        // matched candidates are still removed
+        assert!(store.get_items(&tester.fingerprint(&"dirty room")).is_empty());
+    }
+
     #[test]
     fn integration() {
     let mut adapter = SimpleAdapter::new();
@@ -5,10 +5,14 @@ extern crate serde;
 
 //use std::str;
 use std::env;
-//use std::time::Instant;
+use std::time::Duration;
 
 use gnip_twitter_stream::{load_cred, GnipStream};
-use manga_rs::{SimpleAdapter, AsciiTester, MemoryStore, process_item, filter_all};
+use manga_rs::{SimpleAdapter, AsciiTester, BoundedStore, process_iter, filter_all};
+
+// candidates older than a day are unlikely to be matched
+const MAX_CANDIDATE_AGE_SECS: u64 = 60 * 60 * 24;
+const MAX_FINGERPRINTS: usize = 2_000_000;
 
 fn main() {
     let cred_path = match env::var("TWITTER_CRED_PATH") {
@@ -22,37 +26,18 @@ fn main() {
     let mut streamer = GnipStream::new(url, &cred, 2);
     streamer.run().expect("failed to start stream");
 
-    //let mut count = 0usize;
-    //let mut filt_count = 0usize;
-    //let mut last_print = 0usize;
-    //let start = Instant::now();
     let mut adapter = SimpleAdapter::new();
     let mut tester = AsciiTester::default();
-    let mut store = MemoryStore::new();
+    let max_age = Duration::from_secs(MAX_CANDIDATE_AGE_SECS);
+    let mut store = BoundedStore::new(Some(max_age), Some(MAX_FINGERPRINTS));
     //let mut iter = streamer.flat_map(|item| item.ok());
     //simple_find_anagrams(&mut iter, &mut finder);
 
-    while let Some(stream_result) = streamer.next() {
-        let tweet = match stream_result {
-            Ok(s) => s,
-            Err(e) => { println!("error in stream {:?})", e); return },
-        };
-
-        //count += 1;
-
-        if filter_all(&tweet) {
-            //filt_count += 1;
-            process_item(tweet, &mut store, &mut adapter, &mut tester);
-            //finder.add(&tweet);
-        }
-    }
-        //let elapsed = start.elapsed().as_secs() as usize;
-        //if filt_count % 100 == 0 && filt_count != last_print && count > 0 && elapsed > 0 {
-            //last_print = filt_count;
-            //let tps = count / elapsed;
-            //let passed = (filt_count as f64 / count as f64) * 100 as f64;
-            //println!("count: {}/{} ({:.2}%) secs {} ({} tps)",
-            //filt_count, count, passed, elapsed, tps);
-        //}
-    //}
+    let tweets = streamer
+        .map(|stream_result| stream_result.map_err(|e| println!("error in stream {:?})", e)))
+        .take_while(Result::is_ok)
+        .flat_map(Result::ok);
+    let summary = process_iter(tweets, Some(&mut filter_all), &mut store, &mut adapter, &mut tester);
+    println!("{:?}", summary);
+    println!("{:?}", store.stats());
 }
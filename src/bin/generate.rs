@@ -0,0 +1,42 @@
+extern crate manga_rs;
+
+use std::env;
+use std::process;
+
+use manga_rs::{Generator, GeneratorLimits};
+
+static USAGE: &str = "usage: generate <word list> <text> [max words] [count]";
+
+/// Parses an optional numeric argument, falling back to `default`.
+fn parse_arg(arg: Option<String>, default: usize) -> usize {
+    match arg {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("expected a number, found '{}'\n{}", arg, USAGE);
+            process::exit(1)
+        }),
+        None => default,
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (words, input) = match (args.next(), args.next()) {
+        (Some(words), Some(input)) => (words, input),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1)
+        }
+    };
+
+    let defaults = GeneratorLimits::default();
+    let limits = GeneratorLimits {
+        max_words: parse_arg(args.next(), defaults.max_words),
+        max_results: parse_arg(args.next(), defaults.max_results),
+        ..defaults
+    };
+
+    let generator = Generator::from_path(&words).expect("failed to load word list");
+    for phrase in generator.generate(&input, &limits) {
+        println!("{}", phrase);
+    }
+}
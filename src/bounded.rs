@@ -0,0 +1,237 @@
+use std::cell::{Cell, RefCell};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use lru_cache::LruCache;
+
+use anagrams::{Store, BucketPolicy};
+
+/// Counts of candidates dropped by a `BoundedStore`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvictionStats {
+    /// Candidates dropped for being older than the maximum age.
+    pub expired: usize,
+    /// Candidates dropped because their key was least recently used
+    /// when the store was full.
+    pub evicted: usize,
+}
+
+/// An in memory store with a bounded size.
+///
+/// Candidates older than `max_age` are dropped, and once there are
+/// `max_keys` keys, adding a new key drops the least recently used one.
+///
+/// `max_keys` bounds the number of fingerprints, not candidates; each key
+/// holds up to a `BucketPolicy`'s worth of candidates.
+pub struct BoundedStore<K: Hash + Eq, V> {
+    items: RefCell<LruCache<K, Vec<(Instant, V)>>>,
+    policy: BucketPolicy,
+    max_age: Option<Duration>,
+    max_keys: Option<usize>,
+    last_sweep: Cell<Instant>,
+    /// The time of the last `get_items`, so that `remove_item` indexes
+    /// the same candidates that were returned.
+    last_read: Cell<Instant>,
+    stats: Cell<EvictionStats>,
+}
+
+impl<K: Hash + Eq, V> BoundedStore<K, V> {
+    /// Creates a new store. Either bound may be omitted.
+    pub fn new(max_age: Option<Duration>, max_keys: Option<usize>) -> Self {
+        BoundedStore::with_policy(max_age, max_keys, BucketPolicy::default())
+    }
+
+    pub fn with_policy(max_age: Option<Duration>, max_keys: Option<usize>,
+                       policy: BucketPolicy) -> Self {
+        let capacity = max_keys.unwrap_or(usize::max_value());
+        BoundedStore {
+            items: RefCell::new(LruCache::new(capacity)),
+            policy,
+            max_age,
+            max_keys,
+            last_sweep: Cell::new(Instant::now()),
+            last_read: Cell::new(Instant::now()),
+            stats: Cell::new(EvictionStats::default()),
+        }
+    }
+
+    /// The number of keys in the store.
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+
+    /// Returns the number of candidates dropped so far.
+    pub fn stats(&self) -> EvictionStats {
+        self.stats.get()
+    }
+
+    /// Drops every candidate older than the maximum age.
+    ///
+    /// This happens automatically as items are inserted, at most once per
+    /// `max_age`; candidates which have expired but not yet been dropped
+    /// are never returned.
+    pub fn expire(&mut self) {
+        let max_age = match self.max_age {
+            Some(age) => age,
+            None => return,
+        };
+        let now = Instant::now();
+        let mut expired = 0;
+        {
+            // rebuild the cache, preserving the order of use
+            let mut items = self.items.borrow_mut();
+            let mut kept = LruCache::new(items.capacity());
+            while let Some((key, mut bucket)) = items.remove_lru() {
+                expired += drop_expired(&mut bucket, now, max_age);
+                if !bucket.is_empty() {
+                    kept.insert(key, bucket);
+                }
+            }
+            *items = kept;
+        }
+        self.last_sweep.set(now);
+        self.add_stats(expired, 0);
+    }
+
+    fn is_expired(&self, inserted: Instant, now: Instant) -> bool {
+        self.max_age.map(|age| now.duration_since(inserted) > age).unwrap_or(false)
+    }
+
+    fn add_stats(&self, expired: usize, evicted: usize) {
+        let mut stats = self.stats.get();
+        stats.expired += expired;
+        stats.evicted += evicted;
+        self.stats.set(stats);
+    }
+}
+
+fn drop_expired<V>(bucket: &mut Vec<(Instant, V)>, now: Instant, max_age: Duration) -> usize {
+    let before = bucket.len();
+    bucket.retain(|&(inserted, _)| now.duration_since(inserted) <= max_age);
+    before - bucket.len()
+}
+
+impl<K: Hash + Eq, V: Clone> Store<K, V> for BoundedStore<K, V> {
+    fn remove(&mut self, key: &K) {
+        self.items.borrow_mut().remove(key);
+    }
+
+    fn get_items(&self, key: &K) -> Vec<V> {
+        let now = Instant::now();
+        self.last_read.set(now);
+        let mut items = self.items.borrow_mut();
+        match items.get_mut(key) {
+            Some(bucket) => bucket.iter()
+                .filter(|&&(inserted, _)| !self.is_expired(inserted, now))
+                .map(|&(_, ref value)| value.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn remove_item(&mut self, key: &K, idx: usize) {
+        // indices refer to the unexpired candidates returned by `get_items`
+        let now = self.last_read.get();
+        let mut expired = 0;
+        let is_empty = {
+            let mut items = self.items.borrow_mut();
+            let bucket = match items.get_mut(key) {
+                Some(bucket) => bucket,
+                None => return,
+            };
+            if let Some(age) = self.max_age {
+                expired = drop_expired(bucket, now, age);
+            }
+            if idx < bucket.len() {
+                bucket.remove(idx);
+            }
+            bucket.is_empty()
+        };
+        if is_empty {
+            self.items.borrow_mut().remove(key);
+        }
+        self.add_stats(expired, 0);
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        let now = Instant::now();
+        if let Some(age) = self.max_age {
+            if now.duration_since(self.last_sweep.get()) > age {
+                self.expire();
+            }
+        }
+
+        let mut expired = 0;
+        let mut evicted = 0;
+        {
+            let mut items = self.items.borrow_mut();
+            if items.contains_key(&key) {
+                let bucket = items.get_mut(&key).unwrap();
+                if let Some(age) = self.max_age {
+                    expired += drop_expired(bucket, now, age);
+                }
+                self.policy.add(bucket, (now, value));
+            } else {
+                if let Some(max) = self.max_keys {
+                    while items.len() >= max {
+                        let (_, mut bucket) = match items.remove_lru() {
+                            Some(lru) => lru,
+                            None => break,
+                        };
+                        if let Some(age) = self.max_age {
+                            expired += drop_expired(&mut bucket, now, age);
+                        }
+                        evicted += bucket.len();
+                    }
+                }
+                items.insert(key, vec![(now, value)]);
+            }
+        }
+        self.add_stats(expired, evicted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use super::*;
+
+    #[test]
+    fn max_keys() {
+        let mut store = BoundedStore::new(None, Some(3));
+        store.insert(1, "one");
+        store.insert(2, "two");
+        store.insert(2, "deux");
+        store.insert(3, "three");
+        // touch 1, so that 2 is least recently used
+        assert_eq!(store.get_items(&1), vec!["one"]);
+        store.insert(4, "four");
+        assert_eq!(store.len(), 3);
+        assert!(store.get_items(&2).is_empty());
+        assert_eq!(store.stats(), EvictionStats { expired: 0, evicted: 2 });
+
+        store.remove_item(&1, 0);
+        assert_eq!(store.len(), 2);
+        store.insert(5, "five");
+        assert_eq!(store.stats().evicted, 2);
+    }
+
+    #[test]
+    fn max_age() {
+        let max_age = Duration::from_millis(50);
+        let mut store = BoundedStore::new(Some(max_age), None);
+        store.insert(1, "one");
+        store.insert(2, "two");
+        thread::sleep(max_age * 2);
+        store.insert(2, "deux");
+        assert!(store.get_items(&1).is_empty());
+        assert_eq!(store.get_items(&2), vec!["deux"]);
+        // the insert swept the expired items
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.stats(), EvictionStats { expired: 2, evicted: 0 });
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, Condvar};
+
+use anagrams::{Adapter, Tester, MatchScore};
+
+/// What a `ChannelAdapter` does with a hit when its channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait until the receiver makes room.
+    Block,
+    /// Drop the new hit.
+    DropNewest,
+    /// Drop the oldest hit in the channel, to make room for the new one.
+    DropOldest,
+}
+
+struct Queue<M> {
+    items: VecDeque<M>,
+    dropped: usize,
+    sender_alive: bool,
+    receiver_alive: bool,
+}
+
+struct Shared<M> {
+    queue: Mutex<Queue<M>>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<M> Shared<M> {
+    fn lock<'a>(&'a self) -> MutexGuard<'a, Queue<M>> {
+        self.queue.lock().unwrap()
+    }
+}
+
+/// An adapter which sends each hit, as `(p1, p2, fingerprint)`, to a
+/// `HitReceiver` that may be on another thread.
+pub struct ChannelAdapter<T, F> {
+    shared: Arc<Shared<(T, T, F)>>,
+    backpressure: Backpressure,
+}
+
+/// The receiving end of a `ChannelAdapter`.
+pub struct HitReceiver<T, F> {
+    shared: Arc<Shared<(T, T, F)>>,
+}
+
+/// Creates an adapter and the receiver for its hits. The channel holds at
+/// most `capacity` hits; what happens when it is full is determined by
+/// `backpressure`.
+pub fn hit_channel<T, F>(capacity: usize, backpressure: Backpressure)
+    -> (ChannelAdapter<T, F>, HitReceiver<T, F>)
+{
+    assert!(capacity > 0, "channel capacity must be non-zero");
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            items: VecDeque::with_capacity(capacity),
+            dropped: 0,
+            sender_alive: true,
+            receiver_alive: true,
+        }),
+        capacity,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    let adapter = ChannelAdapter { shared: shared.clone(), backpressure };
+    (adapter, HitReceiver { shared })
+}
+
+impl<T, F> ChannelAdapter<T, F> {
+    /// Sends a hit, applying the backpressure policy if the channel is full.
+    ///
+    /// If the receiver has been dropped the hit is discarded, and counted
+    /// as dropped.
+    pub fn send(&self, p1: T, p2: T, fingerprint: F) {
+        let mut queue = self.shared.lock();
+        if self.backpressure == Backpressure::Block {
+            while queue.receiver_alive && queue.items.len() >= self.shared.capacity {
+                queue = self.shared.not_full.wait(queue).unwrap();
+            }
+        }
+        if !queue.receiver_alive {
+            queue.dropped += 1;
+            return
+        }
+        if queue.items.len() >= self.shared.capacity {
+            queue.dropped += 1;
+            match self.backpressure {
+                Backpressure::DropNewest => return,
+                _ => { queue.items.pop_front(); }
+            }
+        }
+        queue.items.push_back((p1, p2, fingerprint));
+        self.shared.not_empty.notify_one();
+    }
+
+    /// The number of hits dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.shared.lock().dropped
+    }
+}
+
+impl<T, F> Drop for ChannelAdapter<T, F> {
+    fn drop(&mut self) {
+        self.shared.lock().sender_alive = false;
+        self.shared.not_empty.notify_all();
+    }
+}
+
+impl<T, TE> Adapter<T, TE> for ChannelAdapter<T, TE::Fingerprint>
+    where T: Clone,
+          TE: Tester<T>,
+          TE::Fingerprint: Clone,
+{
+    fn handle_match(&mut self, p1: &T, p2: &T, hash: &TE::Fingerprint, _score: &MatchScore) {
+        self.send(p1.clone(), p2.clone(), hash.clone())
+    }
+}
+
+impl<T, F> HitReceiver<T, F> {
+    /// Waits for the next hit. Returns `None` once the adapter has been
+    /// dropped and every hit has been received.
+    pub fn recv(&self) -> Option<(T, T, F)> {
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(hit) = queue.items.pop_front() {
+                self.shared.not_full.notify_one();
+                return Some(hit);
+            }
+            if !queue.sender_alive {
+                return None;
+            }
+            queue = self.shared.not_empty.wait(queue).unwrap();
+        }
+    }
+
+    /// Returns the next hit, if one is waiting.
+    pub fn try_recv(&self) -> Option<(T, T, F)> {
+        let hit = self.shared.lock().items.pop_front();
+        if hit.is_some() {
+            self.shared.not_full.notify_one();
+        }
+        hit
+    }
+
+    /// The number of hits dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.shared.lock().dropped
+    }
+}
+
+impl<T, F> Iterator for HitReceiver<T, F> {
+    type Item = (T, T, F);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl<T, F> Drop for HitReceiver<T, F> {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_alive = false;
+        self.shared.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use super::*;
+    use anagrams::{AsciiTester, AsciiFingerprint, MemoryStore, process_iter};
+
+    const ITEMS: &[&str] = &["dormitory", "dirty room", "astronomer", "moon starer",
+                             "a gentleman", "elegant man"];
+
+    fn run(adapter: &mut ChannelAdapter<&'static str, AsciiFingerprint>) {
+        let mut tester = AsciiTester::default();
+        let mut store = MemoryStore::new();
+        process_iter(ITEMS.iter().cloned(), None, &mut store, adapter, &mut tester);
+    }
+
+    #[test]
+    fn block() {
+        let (mut adapter, receiver) = hit_channel(1, Backpressure::Block);
+        let consumer = thread::spawn(move || {
+            receiver.map(|(p1, p2, _)| (p1, p2)).collect::<Vec<_>>()
+        });
+        run(&mut adapter);
+        assert_eq!(adapter.dropped(), 0);
+        drop(adapter);
+        assert_eq!(consumer.join().unwrap(), vec![("dirty room", "dormitory"),
+                                                  ("moon starer", "astronomer"),
+                                                  ("elegant man", "a gentleman")]);
+    }
+
+    #[test]
+    fn drop_policies() {
+        let (mut adapter, receiver) = hit_channel(1, Backpressure::DropNewest);
+        run(&mut adapter);
+        assert_eq!(receiver.dropped(), 2);
+        let (p1, _, fingerprint) = receiver.try_recv().unwrap();
+        assert_eq!(p1, "dirty room");
+        assert_eq!(fingerprint, AsciiFingerprint::new("dormitory"));
+        assert!(receiver.try_recv().is_none());
+
+        let (mut adapter, receiver) = hit_channel(2, Backpressure::DropOldest);
+        run(&mut adapter);
+        assert_eq!(adapter.dropped(), 1);
+        drop(adapter);
+        let received = receiver.map(|(p1, _, _)| p1).collect::<Vec<_>>();
+        assert_eq!(received, vec!["moon starer", "elegant man"]);
+
+        // hits sent after the receiver is gone are dropped
+        let (mut adapter, receiver) = hit_channel(1, Backpressure::Block);
+        drop(receiver);
+        run(&mut adapter);
+        assert_eq!(adapter.dropped(), 3);
+    }
+}
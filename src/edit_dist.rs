@@ -1,63 +1,480 @@
+use std::mem;
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+const WORD_BITS: usize = 64;
+const HIGH_BIT: u64 = 1 << (WORD_BITS - 1);
+const ASCII_LEN: usize = 128;
+
+/// The units over which edit distance is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceUnit {
+    /// Unicode scalar values.
+    Char,
+    /// Extended grapheme clusters, so that a base letter and its combining
+    /// marks (or a flag, or a family emoji) count as a single edit.
+    Grapheme,
+}
+
+/// The cost of each edit operation, for `EditDistance::weighted_distance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct EditCosts {
+    pub insert: usize,
+    pub delete: usize,
+    pub substitute: usize,
+    /// The cost of swapping two adjacent units. A transposition is only
+    /// used where it is cheaper than the equivalent substitutions.
+    pub transpose: usize,
+}
+
+/// The edit distance metrics we support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Insertions, deletions and substitutions.
+    Levenshtein,
+    /// Levenshtein plus transposition of adjacent units, where no unit
+    /// is edited more than once (optimal string alignment).
+    Damerau,
+    /// Damerau, with custom costs for each operation.
+    Weighted(EditCosts),
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct EditDistance {
+    unit: DistanceUnit,
     storage: Vec<usize>,
+    /// The inputs, as chars or interned grapheme clusters.
+    a_syms: Vec<u32>,
+    b_syms: Vec<u32>,
+    /// For each ascii byte, a bitmask (per block) of its positions in the pattern.
+    peq: Vec<u64>,
+    /// The vertical positive and negative delta vectors for each block.
+    blocks: Vec<(u64, u64)>,
 }
 
 impl EditDistance {
-    // substantially based on https://github.com/febeling/edit-distance,
-    // but with fewer allocations
+    /// Creates a new `EditDistance` measuring over the given `DistanceUnit`.
+    ///
+    /// `EditDistance::default()` measures over chars.
+    pub fn new(unit: DistanceUnit) -> Self {
+        EditDistance { unit, ..Default::default() }
+    }
+
+    /// Returns the Levenshtein distance between `a` and `b`.
+    ///
+    /// Ascii inputs use the bit-parallel algorithm, and other inputs
+    /// fall back to the dynamic programming implementation.
     pub fn distance<A, B>(&mut self, a: A, b: B) -> usize
         where A: AsRef<str>,
               B: AsRef<str>,
     {
         let a = a.as_ref();
         let b = b.as_ref();
-        let start_idx = a.chars().zip(b.chars())
-            .take_while(|&(ac, bc)| ac == bc)
-            .count();
-        // fails if input not ascii
-        let a = &a[start_idx..];
-        let b = &b[start_idx..];
-
-        let a_count = a.chars().count();
-        let b_count = b.chars().count();
-        let nb_cols = b_count + 1;
-
-        // resize storage if needed
-        let nb_items = (a_count + 1) * (b_count + 1);
-
-        if self.storage.len() < nb_items {
-            let to_add = nb_items - self.storage.len();
-            self.storage.reserve(to_add);
-            for _ in 0..to_add {
-                self.storage.push(0);
+        if self.uses_bits(a, b) {
+            self.bit_distance(a, b)
+        } else {
+            self.dp_distance(a, b)
+        }
+    }
+
+    /// Computes the edit distance by filling the full matrix.
+    ///
+    /// This is the reference implementation; mostly exposed for testing
+    /// and benchmarking.
+    pub fn dp_distance<A, B>(&mut self, a: A, b: B) -> usize
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        self.load(a.as_ref(), b.as_ref());
+        let (a, b) = trim_common(&self.a_syms, &self.b_syms);
+        dp(&mut self.storage, a, b)
+    }
+
+    /// Computes the edit distance of two ascii strings using Myers'
+    /// bit-vector algorithm, which processes 64 rows of the matrix at a time.
+    ///
+    /// Patterns longer than 64 bytes are split into blocks, as described
+    /// in Hyyrö, "A bit-vector algorithm for computing Levenshtein and
+    /// Damerau edit distances" (2003).
+    ///
+    /// # Panics
+    ///
+    /// Panics if either input is not ascii.
+    pub fn bit_distance<A, B>(&mut self, a: A, b: B) -> usize
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        let a = a.as_ref();
+        let b = b.as_ref();
+        assert!(a.is_ascii() && b.is_ascii(), "bit_distance requires ascii input");
+        let (a, b) = trim_common(a.as_bytes(), b.as_bytes());
+        // the shorter string is the pattern, so we need fewer blocks
+        let (pattern, text) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+        if pattern.is_empty() { return text.len() }
+
+        let nb_blocks = (pattern.len() + WORD_BITS - 1) / WORD_BITS;
+        self.peq.clear();
+        self.peq.resize(ASCII_LEN * nb_blocks, 0);
+        for (i, &byte) in pattern.iter().enumerate() {
+            self.peq[byte as usize * nb_blocks + i / WORD_BITS] |= 1 << (i % WORD_BITS);
+        }
+
+        if nb_blocks == 1 {
+            return myers_single(&self.peq, pattern.len(), text)
+        }
+
+        self.blocks.clear();
+        self.blocks.resize(nb_blocks, (!0, 0));
+        let last_bit = 1 << ((pattern.len() - 1) % WORD_BITS);
+        let mut score = pattern.len();
+        for &byte in text {
+            let peq = &self.peq[byte as usize * nb_blocks..];
+            // the top row of the matrix increases by one in each column
+            let mut carry = 1;
+            for (idx, block) in self.blocks.iter_mut().enumerate() {
+                let high_bit = if idx == nb_blocks - 1 { last_bit } else { HIGH_BIT };
+                carry = advance_block(block, peq[idx], carry, high_bit);
             }
+            score = (score as isize + carry as isize) as usize;
         }
+        score
+    }
 
-        // initial values
-        for i in 0..a_count {
-            let idx = row_col_to_idx(i, 0, nb_cols);
-            let idx1 = row_col_to_idx(i+1, 0, nb_cols);
-            self.storage[idx1] = self.storage[idx] + 1;
+    /// Returns the edit distance between `a` and `b` if it is no greater
+    /// than `max`, or `None` otherwise.
+    ///
+    /// This only computes the diagonal band of the matrix that could
+    /// produce a result within `max` (Ukkonen's cutoff), and returns as
+    /// soon as every cell in a row exceeds `max`.
+    pub fn distance_within<A, B>(&mut self, a: A, b: B, max: usize) -> Option<usize>
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        let a = a.as_ref();
+        let b = b.as_ref();
+        // for short ascii inputs, computing the exact distance is cheaper
+        if self.uses_bits(a, b) && a.len().min(b.len()) <= WORD_BITS {
+            let dist = self.bit_distance(a, b);
+            return if dist <= max { Some(dist) } else { None };
         }
 
-        for i in 0..b_count {
-            self.storage[i+1] = self.storage[i] + 1;
+        self.load(a, b);
+        let (a, b) = trim_common(&self.a_syms, &self.b_syms);
+        banded(&mut self.storage, a, b, max)
+    }
+
+    /// Returns the optimal string alignment distance between `a` and `b`.
+    ///
+    /// This is Levenshtein distance where swapping two adjacent units
+    /// counts as a single edit, so "form" and "from" have distance 1.
+    pub fn osa_distance<A, B>(&mut self, a: A, b: B) -> usize
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        self.weighted_distance(a, b, &EditCosts::default())
+    }
+
+    /// Returns the optimal string alignment distance between `a` and `b`,
+    /// with each operation weighted according to `costs`.
+    pub fn weighted_distance<A, B>(&mut self, a: A, b: B, costs: &EditCosts) -> usize
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        self.load(a.as_ref(), b.as_ref());
+        let (a, b) = trim_common(&self.a_syms, &self.b_syms);
+        weighted_dp(&mut self.storage, a, b, costs)
+    }
+
+    /// Returns the distance between `a` and `b` under `metric`.
+    pub fn metric_distance<A, B>(&mut self, metric: &DistanceMetric, a: A, b: B) -> usize
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        match *metric {
+            DistanceMetric::Levenshtein => self.distance(a, b),
+            DistanceMetric::Damerau => self.osa_distance(a, b),
+            DistanceMetric::Weighted(ref costs) => self.weighted_distance(a, b, costs),
         }
+    }
+
+    /// Returns the distance between `a` and `b` under `metric`, if it is
+    /// no greater than `max`.
+    pub fn metric_within<A, B>(&mut self, metric: &DistanceMetric,
+                               a: A, b: B, max: usize) -> Option<usize>
+        where A: AsRef<str>,
+              B: AsRef<str>,
+    {
+        let dist = match *metric {
+            DistanceMetric::Levenshtein => return self.distance_within(a, b, max),
+            DistanceMetric::Damerau => self.osa_distance(a, b),
+            DistanceMetric::Weighted(ref costs) => self.weighted_distance(a, b, costs),
+        };
+        if dist <= max { Some(dist) } else { None }
+    }
+
+    /// Whether we can use the (ascii only) bit-parallel implementation.
+    ///
+    /// In ascii, chars and graphemes differ only in the treatment of "\r\n".
+    fn uses_bits(&self, a: &str, b: &str) -> bool {
+        a.is_ascii() && b.is_ascii() &&
+            (self.unit == DistanceUnit::Char || !(a.contains('\r') || b.contains('\r')))
+    }
 
-        for (i, ca) in a.chars().enumerate() {
-            for (j, cb) in b.chars().enumerate() {
-                let alt1 = self.storage[row_col_to_idx(i, j+1, nb_cols)] + 1;
-                let alt2 = self.storage[row_col_to_idx(i+1, j, nb_cols)] + 1;
-                let alt3 = self.storage[row_col_to_idx(i, j, nb_cols)] + if ca == cb { 0 } else { 1 };
-                let min_alt = alt1.min(alt2).min(alt3);
-                self.storage[row_col_to_idx(i+1, j+1, nb_cols)] = min_alt;
+    /// Converts the inputs to symbols, according to our `DistanceUnit`.
+    fn load(&mut self, a: &str, b: &str) {
+        self.a_syms.clear();
+        self.b_syms.clear();
+        match self.unit {
+            DistanceUnit::Char => {
+                self.a_syms.extend(a.chars().map(|c| c as u32));
+                self.b_syms.extend(b.chars().map(|c| c as u32));
             }
+            DistanceUnit::Grapheme => {
+                let mut interned = HashMap::new();
+                intern_graphemes(a, &mut interned, &mut self.a_syms);
+                intern_graphemes(b, &mut interned, &mut self.b_syms);
+            }
+        }
+    }
+}
+
+impl EditCosts {
+    /// The most that editing a single unit can cost. A substitution never
+    /// costs more than a deletion and an insertion.
+    pub fn max_cost(&self) -> usize {
+        let substitute = self.substitute.min(self.insert + self.delete);
+        self.insert.max(self.delete).max(substitute).max(1)
+    }
+}
+
+impl DistanceMetric {
+    /// The most that editing a single unit can cost, so that distances
+    /// can be compared to the length of the input.
+    pub fn max_cost(&self) -> usize {
+        match *self {
+            DistanceMetric::Weighted(ref costs) => costs.max_cost(),
+            _ => 1,
         }
-        self.storage[row_col_to_idx(a_count, b_count, nb_cols)]
     }
 }
 
+impl Default for EditCosts {
+    fn default() -> Self {
+        EditCosts { insert: 1, delete: 1, substitute: 1, transpose: 1 }
+    }
+}
+
+impl Default for DistanceMetric {
+    fn default() -> Self {
+        DistanceMetric::Levenshtein
+    }
+}
+
+impl Default for DistanceUnit {
+    fn default() -> Self {
+        DistanceUnit::Char
+    }
+}
+
+/// Assigns each distinct grapheme cluster in `s` an id, shared across inputs.
+fn intern_graphemes<'a>(s: &'a str, interned: &mut HashMap<&'a str, u32>, syms: &mut Vec<u32>) {
+    for grapheme in s.graphemes(true) {
+        let next_id = interned.len() as u32;
+        syms.push(*interned.entry(grapheme).or_insert(next_id));
+    }
+}
+
+/// Strips the common prefix and suffix from two slices, which never
+/// affect the edit distance.
+fn trim_common<'a, T: PartialEq>(a: &'a [T], b: &'a [T]) -> (&'a [T], &'a [T]) {
+    let prefix_len = a.iter().zip(b.iter())
+        .take_while(|&(ac, bc)| ac == bc)
+        .count();
+    let (a, b) = (&a[prefix_len..], &b[prefix_len..]);
+    let suffix_len = a.iter().rev().zip(b.iter().rev())
+        .take_while(|&(ac, bc)| ac == bc)
+        .count();
+    (&a[..a.len() - suffix_len], &b[..b.len() - suffix_len])
+}
+
+// substantially based on https://github.com/febeling/edit-distance,
+// but with fewer allocations
+fn dp(storage: &mut Vec<usize>, a: &[u32], b: &[u32]) -> usize {
+    let a_count = a.len();
+    let b_count = b.len();
+    let nb_cols = b_count + 1;
+
+    // resize storage if needed
+    let nb_items = (a_count + 1) * (b_count + 1);
+    if storage.len() < nb_items {
+        storage.resize(nb_items, 0);
+    }
+
+    // initial values
+    for i in 0..a_count + 1 {
+        storage[row_col_to_idx(i, 0, nb_cols)] = i;
+    }
+    for j in 0..b_count + 1 {
+        storage[j] = j;
+    }
+
+    for (i, ca) in a.iter().enumerate() {
+        for (j, cb) in b.iter().enumerate() {
+            let alt1 = storage[row_col_to_idx(i, j+1, nb_cols)] + 1;
+            let alt2 = storage[row_col_to_idx(i+1, j, nb_cols)] + 1;
+            let alt3 = storage[row_col_to_idx(i, j, nb_cols)] + if ca == cb { 0 } else { 1 };
+            let min_alt = alt1.min(alt2).min(alt3);
+            storage[row_col_to_idx(i+1, j+1, nb_cols)] = min_alt;
+        }
+    }
+    storage[row_col_to_idx(a_count, b_count, nb_cols)]
+}
+
+/// The full matrix algorithm, with weights and transpositions.
+fn weighted_dp(storage: &mut Vec<usize>, a: &[u32], b: &[u32], costs: &EditCosts) -> usize {
+    let a_count = a.len();
+    let b_count = b.len();
+    let nb_cols = b_count + 1;
+
+    let nb_items = (a_count + 1) * (b_count + 1);
+    if storage.len() < nb_items {
+        storage.resize(nb_items, 0);
+    }
+
+    for i in 0..a_count + 1 {
+        storage[row_col_to_idx(i, 0, nb_cols)] = i * costs.delete;
+    }
+    for j in 0..b_count + 1 {
+        storage[j] = j * costs.insert;
+    }
+
+    for (i, ca) in a.iter().enumerate() {
+        for (j, cb) in b.iter().enumerate() {
+            let delete = storage[row_col_to_idx(i, j+1, nb_cols)] + costs.delete;
+            let insert = storage[row_col_to_idx(i+1, j, nb_cols)] + costs.insert;
+            let sub_cost = if ca == cb { 0 } else { costs.substitute };
+            let substitute = storage[row_col_to_idx(i, j, nb_cols)] + sub_cost;
+            let mut min_alt = delete.min(insert).min(substitute);
+            if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+                let transpose = storage[row_col_to_idx(i-1, j-1, nb_cols)] + costs.transpose;
+                min_alt = min_alt.min(transpose);
+            }
+            storage[row_col_to_idx(i+1, j+1, nb_cols)] = min_alt;
+        }
+    }
+    storage[row_col_to_idx(a_count, b_count, nb_cols)]
+}
+
+/// Computes the diagonal band of the matrix within `max` of the main
+/// diagonal, returning early if every cell in a row exceeds `max`.
+fn banded(storage: &mut Vec<usize>, a: &[u32], b: &[u32], max: usize) -> Option<usize> {
+    let a_count = a.len();
+    let b_count = b.len();
+    let len_diff = if a_count > b_count { a_count - b_count } else { b_count - a_count };
+    if len_diff > max { return None }
+    if a_count == 0 { return Some(b_count) }
+
+    // anything larger than max is out of the band; we clamp to this
+    let too_far = max + 1;
+    let nb_cols = b_count + 1;
+    if storage.len() < nb_cols * 2 {
+        storage.resize(nb_cols * 2, 0);
+    }
+    let (prev, cur) = storage.split_at_mut(nb_cols);
+    let mut prev = &mut prev[..nb_cols];
+    let mut cur = &mut cur[..nb_cols];
+
+    for (j, cell) in prev.iter_mut().enumerate() {
+        *cell = j.min(too_far);
+    }
+
+    for (i, ca) in a.iter().enumerate() {
+        let row = i + 1;
+        let first_col = if row > max { row - max } else { 1 };
+        let last_col = b_count.min(row + max);
+
+        cur[first_col - 1] = if first_col == 1 { row.min(too_far) } else { too_far };
+        let mut row_min = cur[first_col - 1];
+        for j in first_col..last_col + 1 {
+            let cost = if *ca == b[j - 1] { 0 } else { 1 };
+            let val = (prev[j - 1] + cost)
+                .min(prev[j] + 1)
+                .min(cur[j - 1] + 1)
+                .min(too_far);
+            cur[j] = val;
+            row_min = row_min.min(val);
+        }
+        // the next row reads one column further right
+        if last_col < b_count {
+            cur[last_col + 1] = too_far;
+        }
+        if row_min > max { return None }
+        mem::swap(&mut prev, &mut cur);
+    }
+
+    let result = prev[b_count];
+    if result <= max { Some(result) } else { None }
+}
+
+/// Myers' algorithm, for patterns of at most 64 bytes.
+fn myers_single(peq: &[u64], pattern_len: usize, text: &[u8]) -> usize {
+    let last_bit = 1 << (pattern_len - 1);
+    let mut pv: u64 = !0;
+    let mut mv: u64 = 0;
+    let mut score = pattern_len;
+    for &byte in text {
+        let eq = peq[byte as usize];
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+        let ph = mv | !(xh | pv);
+        let mh = pv & xh;
+        if ph & last_bit != 0 {
+            score += 1;
+        } else if mh & last_bit != 0 {
+            score -= 1;
+        }
+        let ph = (ph << 1) | 1;
+        let mh = mh << 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
+    }
+    score
+}
+
+/// Advances one block of the bit-vector algorithm by a column, given
+/// the horizontal delta (-1, 0 or 1) entering at the top of the block.
+/// Returns the horizontal delta leaving the bottom of the block,
+/// as measured at `high_bit`.
+#[inline]
+fn advance_block(block: &mut (u64, u64), eq: u64, h_in: i8, high_bit: u64) -> i8 {
+    let (pv, mv) = *block;
+    let xv = eq | mv;
+    let eq = if h_in < 0 { eq | 1 } else { eq };
+    let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+    let ph = mv | !(xh | pv);
+    let mh = pv & xh;
+
+    let h_out = if ph & high_bit != 0 {
+        1
+    } else if mh & high_bit != 0 {
+        -1
+    } else {
+        0
+    };
+
+    let mut ph = ph << 1;
+    let mut mh = mh << 1;
+    if h_in < 0 {
+        mh |= 1;
+    } else if h_in > 0 {
+        ph |= 1;
+    }
+    *block = (mh | !(xv | ph), ph & xv);
+    h_out
+}
+
 #[inline(always)]
 fn row_col_to_idx(row: usize, col: usize, col_nb: usize) -> usize {
     row * col_nb + col
@@ -84,4 +501,197 @@ mod tests {
             assert_eq!(ed.distance(a, b), exp);
         }
     }
+
+    #[test]
+    fn within() {
+        let mut ed = EditDistance::default();
+        let tests = [
+            ("hello", "bellow"),
+            ("my friend", "remains"),
+            ("this", "that"),
+            ("heaven", "is a place on earth"),
+            ("a fundamentally", "non-creative person"),
+            ("is writing", "these test cases"),
+            ("", "abc"),
+            ("same", "same"),
+            ("kitten", "sitting"),
+            ("flaw", "lawn"),
+        ];
+
+        for &(a, b) in tests.iter() {
+            let exp = ed.distance(a, b);
+            for max in 0..exp + 3 {
+                let within = if exp <= max { Some(exp) } else { None };
+                assert_eq!(ed.distance_within(a, b, max), within, "{} / {} ({})", a, b, max);
+                assert_eq!(ed.distance_within(b, a, max), within, "{} / {} ({})", b, a, max);
+            }
+        }
+    }
+
+    #[test]
+    fn long_inputs() {
+        let mut ed = EditDistance::default();
+        let a = "Our Wellness Doctors are highly experienced,trained by a specialist from Australia.";
+        let b = "Unique diagnostic methods are used for health evaluation, by our wellness doctors.";
+        assert!(a.len() > 64 && b.len() > 64);
+        assert_eq!(ed.bit_distance(a, b), ed.dp_distance(a, b));
+        let a = a.repeat(3);
+        let b = b.repeat(3);
+        assert_eq!(ed.bit_distance(&a, &b), ed.dp_distance(&a, &b));
+    }
+
+    quickcheck! {
+        fn bit_matches_dp(a: String, b: String) -> bool {
+            // a small alphabet gives more interesting alignments
+            let a = small_alphabet(&a);
+            let b = small_alphabet(&b);
+            let mut ed = EditDistance::default();
+            ed.bit_distance(&a, &b) == ed.dp_distance(&a, &b)
+        }
+
+        fn bit_matches_dp_multi_block(a: String, b: String) -> bool {
+            let a = small_alphabet(&a).repeat(3);
+            let b = small_alphabet(&b).repeat(2);
+            let mut ed = EditDistance::default();
+            ed.bit_distance(&a, &b) == ed.dp_distance(&a, &b)
+        }
+    }
+
+    fn small_alphabet(s: &str) -> String {
+        s.chars().map(|c| (b'a' + (c as u32 % 5) as u8) as char).collect()
+    }
+
+    #[test]
+    fn non_ascii() {
+        let mut ed = EditDistance::default();
+        assert_eq!(ed.distance("апельсин", "апельсины"), 1);
+        assert_eq!(ed.distance("naïve", "naive"), 1);
+        // common prefix and suffix are both multi-byte
+        assert_eq!(ed.distance("привет мир", "превед мир"), 2);
+        assert_eq!(ed.distance("😒 so over it 😒", "😒 so ovr it 😒"), 1);
+        assert_eq!(ed.distance_within("привет мир", "превед мир", 1), None);
+        assert_eq!(ed.distance_within("привет мир", "превед мир", 2), Some(2));
+        assert_eq!(ed.distance("", "日本語"), 3);
+    }
+
+    #[test]
+    fn graphemes() {
+        let mut chars = EditDistance::default();
+        let mut graphemes = EditDistance::new(DistanceUnit::Grapheme);
+        // 'e' + combining diaeresis
+        let one = "noe\u{308}l";
+        let two = "noel";
+        assert_eq!(chars.distance(one, two), 1);
+        assert_eq!(graphemes.distance(one, two), 1);
+        let two = "noël";
+        assert_eq!(chars.distance(one, two), 2);
+        assert_eq!(graphemes.distance(one, two), 1);
+
+        // flags are pairs of regional indicators
+        assert_eq!(chars.distance("go 🇩🇪!", "go 🇫🇷!"), 2);
+        assert_eq!(graphemes.distance("go 🇩🇪!", "go 🇫🇷!"), 1);
+        assert_eq!(graphemes.distance_within("go 🇩🇪!", "go 🇫🇷!", 1), Some(1));
+
+        assert_eq!(chars.distance("a\r\nb", "a\nb"), 1);
+        assert_eq!(graphemes.distance("a\r\nb", "a\nb"), 1);
+        assert_eq!(graphemes.distance("a\r\nb", "a\r\r\nb"), 1);
+    }
+
+    #[test]
+    fn transpositions() {
+        let mut ed = EditDistance::default();
+        assert_eq!(ed.distance("form", "from"), 2);
+        assert_eq!(ed.osa_distance("form", "from"), 1);
+        assert_eq!(ed.osa_distance("ab", "ba"), 1);
+        // OSA doesn't allow editing a transposed pair again
+        assert_eq!(ed.osa_distance("ca", "abc"), 3);
+        for &(a, b) in [("hello", "bellow"), ("this", "that")].iter() {
+            assert_eq!(ed.osa_distance(a, b), ed.distance(a, b));
+        }
+
+        let costs = EditCosts { insert: 2, delete: 2, substitute: 3, transpose: 1 };
+        assert_eq!(ed.weighted_distance("form", "from", &costs), 1);
+        assert_eq!(ed.weighted_distance("this", "that", &costs), 6);
+        assert_eq!(ed.weighted_distance("cat", "cats", &costs), 2);
+        assert_eq!(costs.max_cost(), 3);
+        let costs = EditCosts { insert: 1, delete: 1, substitute: 5, transpose: 5 };
+        // delete + insert is cheaper than substitution
+        assert_eq!(ed.weighted_distance("this", "that", &costs), 4);
+        assert_eq!(DistanceMetric::Weighted(costs).max_cost(), 2);
+        assert_eq!(DistanceMetric::Damerau.max_cost(), 1);
+
+        let metric = DistanceMetric::Damerau;
+        assert_eq!(ed.metric_within(&metric, "form", "from", 1), Some(1));
+        let metric = DistanceMetric::Levenshtein;
+        assert_eq!(ed.metric_within(&metric, "form", "from", 1), None);
+        assert_eq!(ed.metric_distance(&metric, "form", "from"), 2);
+    }
+
+    /// The textbook algorithm, with no trimming or buffer reuse.
+    fn naive_distance(a: &str, b: &str) -> usize {
+        let a = a.chars().collect::<Vec<_>>();
+        let b = b.chars().collect::<Vec<_>>();
+        let mut prev = (0..b.len() + 1).collect::<Vec<_>>();
+        for i in 0..a.len() {
+            let mut cur = vec![i + 1];
+            for j in 0..b.len() {
+                let sub = prev[j] + if a[i] == b[j] { 0 } else { 1 };
+                cur.push(sub.min(prev[j + 1] + 1).min(cur[j] + 1));
+            }
+            prev = cur;
+        }
+        prev[b.len()]
+    }
+
+    /// Textbook weighted optimal string alignment.
+    fn naive_weighted(a: &str, b: &str, costs: &EditCosts) -> usize {
+        let a = a.chars().collect::<Vec<_>>();
+        let b = b.chars().collect::<Vec<_>>();
+        let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 0..a.len() + 1 { d[i][0] = i * costs.delete }
+        for j in 0..b.len() + 1 { d[0][j] = j * costs.insert }
+        for i in 1..a.len() + 1 {
+            for j in 1..b.len() + 1 {
+                let sub = if a[i - 1] == b[j - 1] { 0 } else { costs.substitute };
+                d[i][j] = (d[i - 1][j] + costs.delete)
+                    .min(d[i][j - 1] + costs.insert)
+                    .min(d[i - 1][j - 1] + sub);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    d[i][j] = d[i][j].min(d[i - 2][j - 2] + costs.transpose);
+                }
+            }
+        }
+        d[a.len()][b.len()]
+    }
+
+    quickcheck! {
+        fn weighted_matches_naive(a: String, b: String, costs: (u8, u8, u8, u8)) -> bool {
+            let a = small_alphabet(&a);
+            let b = small_alphabet(&b);
+            let (insert, delete, substitute, transpose) = costs;
+            let costs = EditCosts {
+                insert: insert as usize % 4 + 1,
+                delete: delete as usize % 4 + 1,
+                substitute: substitute as usize % 4 + 1,
+                transpose: transpose as usize % 4 + 1,
+            };
+            let mut ed = EditDistance::default();
+            ed.weighted_distance(&a, &b, &costs) == naive_weighted(&a, &b, &costs) &&
+                ed.osa_distance(&a, &b) == naive_weighted(&a, &b, &EditCosts::default())
+        }
+
+        fn unicode_matches_naive(a: String, b: String) -> bool {
+            let mut ed = EditDistance::default();
+            let exp = naive_distance(&a, &b);
+            ed.distance(&a, &b) == exp && ed.distance_within(&a, &b, exp) == Some(exp)
+        }
+
+        fn shared_affixes(a: String, b: String, affix: String) -> bool {
+            let mut ed = EditDistance::default();
+            let exp = ed.distance(&a, &b);
+            let a = format!("{}{}{}", affix, a, affix);
+            let b = format!("{}{}{}", affix, b, affix);
+            ed.distance(&a, &b) == exp
+        }
+    }
 }
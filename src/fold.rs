@@ -0,0 +1,74 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Letters that have no compatibility decomposition, but which we
+/// want to treat as (sequences of) plain ascii letters.
+fn expand_ligature(chr: char) -> Option<&'static str> {
+    let expanded = match chr {
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ß' => "ss",
+        'ẞ' => "SS",
+        'ø' => "o",
+        'Ø' => "O",
+        'ł' => "l",
+        'Ł' => "L",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ı' => "i",
+        'ŋ' => "ng",
+        'Ŋ' => "NG",
+        _ => return None,
+    };
+    Some(expanded)
+}
+
+/// Folds latin-script text towards ascii.
+///
+/// The input is NFKD normalized, combining marks (accents, cedillas, etc)
+/// are removed, and common ligatures and special letters are expanded,
+/// so that "Straße" becomes "Strasse" and "naïve" becomes "naive".
+/// Characters which are not letters (whitespace, punctuation, emoji) are
+/// left in place.
+pub fn fold_latin<T: AsRef<str>>(s: T) -> String {
+    let s = s.as_ref();
+    let mut result = String::with_capacity(s.len());
+    for chr in s.nfkd().filter(|c| !is_combining_mark(*c)) {
+        match expand_ligature(chr) {
+            Some(expanded) => result.push_str(expanded),
+            None => result.push(chr),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diacritics() {
+        assert_eq!(fold_latin("café"), "cafe");
+        assert_eq!(fold_latin("naïve"), "naive");
+        assert_eq!(fold_latin("Ångström"), "Angstrom");
+        assert_eq!(fold_latin("façade"), "facade");
+    }
+
+    #[test]
+    fn ligatures() {
+        assert_eq!(fold_latin("Straße"), "Strasse");
+        assert_eq!(fold_latin("encyclopædia"), "encyclopaedia");
+        assert_eq!(fold_latin("Œuvre"), "OEuvre");
+        assert_eq!(fold_latin("ﬁne"), "fine");
+        assert_eq!(fold_latin("Łódź"), "Lodz");
+    }
+
+    #[test]
+    fn non_letters_preserved() {
+        assert_eq!(fold_latin("où est-il? 😒"), "ou est-il? 😒");
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use anagrams::AsciiFingerprint;
+
+/// Limits on the phrases produced by a `Generator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratorLimits {
+    /// The most words in a single phrase.
+    pub max_words: usize,
+    /// Words with fewer letters than this are not used.
+    pub min_word_len: usize,
+    /// Stop after finding this many phrases.
+    pub max_results: usize,
+}
+
+/// Finds multi-word anagrams of some text, using the words in a word list.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    /// Words grouped by fingerprint, so that interchangeable words are
+    /// only searched once.
+    groups: Vec<(AsciiFingerprint, Vec<String>)>,
+}
+
+impl Default for GeneratorLimits {
+    fn default() -> Self {
+        GeneratorLimits {
+            max_words: 3,
+            min_word_len: 2,
+            max_results: 100,
+        }
+    }
+}
+
+impl Generator {
+    /// Creates a generator from a list of words.
+    ///
+    /// Words are lowercased; entries which are empty or contain anything
+    /// other than ascii letters and apostrophes are ignored.
+    pub fn new<I, S>(words: I) -> Self
+        where I: IntoIterator<Item=S>,
+              S: AsRef<str>,
+    {
+        let mut index: HashMap<AsciiFingerprint, Vec<String>> = HashMap::new();
+        for word in words {
+            let word = word.as_ref().trim().to_lowercase();
+            if !is_valid_word(&word) { continue }
+            let fingerprint = AsciiFingerprint::new(&word);
+            let bucket = index.entry(fingerprint).or_default();
+            if !bucket.contains(&word) {
+                bucket.push(word);
+            }
+        }
+
+        let mut groups = index.into_iter().collect::<Vec<_>>();
+        for &mut (_, ref mut words) in groups.iter_mut() {
+            words.sort();
+        }
+        // longer words first, so that early results use fewer words
+        groups.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.1.cmp(&b.1)));
+        Generator { groups }
+    }
+
+    /// Loads a word list with one word per line.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let words = file.lines().collect::<io::Result<Vec<_>>>()?;
+        Ok(Generator::new(words))
+    }
+
+    /// The number of distinct words in the list.
+    pub fn word_count(&self) -> usize {
+        self.groups.iter().map(|g| g.1.len()).sum()
+    }
+
+    /// Returns phrases which use exactly the letters of `input`.
+    ///
+    /// Each phrase is a space-separated list of words; phrases which are
+    /// just a reordering of the words in `input` are skipped.
+    pub fn generate(&self, input: &str, limits: &GeneratorLimits) -> Vec<String> {
+        let target = AsciiFingerprint::new(input);
+        let mut results = Vec::new();
+        if target.is_empty() || limits.max_words == 0 || limits.max_results == 0 {
+            return results
+        }
+
+        let candidates = self.groups.iter()
+            .filter(|g| g.0.len() >= limits.min_word_len && target.contains(&g.0))
+            .map(|g| (&g.0, g.1.iter().map(String::as_str).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        let mut input_words = words_of(input);
+        input_words.sort();
+        let mut search = Search {
+            candidates: &candidates,
+            limits,
+            input_words,
+            phrase: Vec::new(),
+            results: &mut results,
+        };
+        let groups = (0..candidates.len()).collect::<Vec<_>>();
+        search.run(&target, &groups, (0, 0));
+        results
+    }
+}
+
+/// The state of a depth-first search for phrases.
+struct Search<'a> {
+    candidates: &'a [(&'a AsciiFingerprint, Vec<&'a str>)],
+    limits: &'a GeneratorLimits,
+    input_words: Vec<String>,
+    phrase: Vec<&'a str>,
+    results: &'a mut Vec<String>,
+}
+
+impl<'a> Search<'a> {
+    fn is_done(&self) -> bool {
+        self.results.len() >= self.limits.max_results
+    }
+
+    /// Extends the current phrase with words from `groups` (indices into
+    /// `candidates`, each of which fits in `remaining`), starting at `start`
+    /// (a group and word index), so that each combination is only visited once.
+    fn run(&mut self, remaining: &AsciiFingerprint, groups: &[usize], start: (usize, usize)) {
+        if remaining.is_empty() {
+            self.add_result();
+            return
+        }
+        if self.phrase.len() >= self.limits.max_words { return }
+
+        let candidates = self.candidates;
+        let last_word = self.phrase.len() + 1 == self.limits.max_words;
+        for (i, &group_idx) in groups.iter().enumerate() {
+            let (fingerprint, ref words) = candidates[group_idx];
+            let rest = match remaining.subtract(fingerprint) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            // only groups which still fit can follow; if none do, or there's
+            // no room for another word, this group can't finish a phrase
+            let next = if rest.is_empty() || last_word {
+                Vec::new()
+            } else {
+                groups[i..].iter().cloned()
+                    .filter(|&g| rest.contains(candidates[g].0))
+                    .collect::<Vec<_>>()
+            };
+            if !rest.is_empty() && next.is_empty() { continue }
+
+            let first = if group_idx == start.0 { start.1 } else { 0 };
+            for (word_idx, word) in words.iter().enumerate().skip(first) {
+                self.phrase.push(word);
+                self.run(&rest, &next, (group_idx, word_idx));
+                self.phrase.pop();
+                if self.is_done() { return }
+            }
+        }
+    }
+
+    fn add_result(&mut self) {
+        let mut words = self.phrase.iter().map(|w| w.replace('\'', "")).collect::<Vec<_>>();
+        words.sort();
+        if words != self.input_words {
+            self.results.push(self.phrase.join(" "));
+        }
+    }
+}
+
+fn is_valid_word(word: &str) -> bool {
+    word.chars().any(|c| c.is_ascii_lowercase())
+        && word.chars().all(|c| c.is_ascii_lowercase() || c == '\'')
+}
+
+/// The lowercased ascii words in `s`.
+fn words_of(s: &str) -> Vec<String> {
+    s.split(|c: char| !(c.is_ascii_alphabetic() || c == '\''))
+        .map(|w| w.replace('\'', "").to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &[&str] = &[
+        "dirty", "room", "moor", "dormitory", "I", "a", "to", "ox",
+        "Rot", "dim", "dory", "tidy", "mir", "x-ray", "",
+    ];
+
+    #[test]
+    fn word_list() {
+        let generator = Generator::new(WORDS);
+        // "x-ray" and "" are skipped
+        assert_eq!(generator.word_count(), 13);
+    }
+
+    #[test]
+    fn generate() {
+        let generator = Generator::new(WORDS);
+        let limits = GeneratorLimits { max_words: 2, ..Default::default() };
+        let mut results = generator.generate("Dormitory!", &limits);
+        results.sort();
+        assert_eq!(results, vec!["dirty moor", "dirty room"]);
+
+        let limits = GeneratorLimits { max_words: 3, ..Default::default() };
+        let results = generator.generate("dormitory", &limits);
+        assert!(results.contains(&"dory mir to".to_string()));
+        assert!(results.iter().all(|r| r.split(' ').count() <= 3));
+
+        // the input's own words are not a result
+        let results = generator.generate("dirty room", &limits);
+        assert!(results.contains(&"dormitory".to_string()));
+        assert!(!results.contains(&"dirty room".to_string()));
+        assert!(!results.contains(&"room dirty".to_string()));
+    }
+
+    #[test]
+    fn limits() {
+        let generator = Generator::new(WORDS);
+        let limits = GeneratorLimits { max_words: 4, min_word_len: 3, max_results: 100 };
+        let results = generator.generate("dormitory", &limits);
+        assert!(!results.is_empty());
+        for phrase in results.iter() {
+            assert!(phrase.split(' ').all(|w| w.len() >= 3), "{}", phrase);
+        }
+
+        let limits = GeneratorLimits { max_results: 1, ..limits };
+        assert_eq!(generator.generate("dormitory", &limits).len(), 1);
+        assert!(generator.generate("zzz", &limits).is_empty());
+        assert!(generator.generate("", &limits).is_empty());
+    }
+}
@@ -1,20 +1,51 @@
 extern crate gnip_twitter_stream;
 extern crate serde_json;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate chrono;
 extern crate flate2;
 extern crate gnudbm;
+extern crate libc;
+extern crate bincode;
 extern crate lru_cache;
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
 #[cfg(test)]
 extern crate tempdir;
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
 
 mod filters;
 mod anagrams;
 mod utils;
 mod mdbm;
+mod logchunk;
+mod wal;
 mod edit_dist;
+mod fold;
+mod script;
+mod generate;
+mod subset;
+mod sharded;
+mod bounded;
+mod adapters;
+mod channel;
 
 pub use utils::write_saved;
 pub use filters::filter_all;
-pub use anagrams::{AsStr, SimpleAdapter, Store, Adapter, Tester, AsciiTester, MemoryStore, EditDistance, process_item, check_item};
-pub use mdbm::Mdbm;
+pub use anagrams::{AsStr, SimpleAdapter, Store, TryStore, Fingerprint, BucketPolicy, Eviction, Adapter, Tester, Rejection, NearTester, AsciiFingerprint, LetterOffset, MatchScore, AsciiTester, AsciiTesterConfig, NormalizeBy, LatinTester, ScriptTester, MemoryStore, EditDistance, process_item, process_item_near, check_item, process_iter, check_iter, BatchSummary};
+pub use mdbm::{Mdbm, LogStore, ChunkedStore, Chunk, ReadChunk, GdbmChunk, GdbmReader, MdbmError,
+               RetentionPolicy, ChunkStats, Entries, StoreReader, convert_dbm_chunks};
+pub use logchunk::LogChunk;
+pub use edit_dist::{DistanceUnit, DistanceMetric, EditCosts};
+pub use fold::fold_latin;
+pub use script::{Script, ScriptFingerprint};
+pub use generate::{Generator, GeneratorLimits};
+pub use subset::{SubsetIndex, process_item_subsets};
+pub use sharded::{ShardedStore, process_item_shared};
+pub use bounded::{BoundedStore, EvictionStats};
+pub use adapters::{StatsAdapter, StatsReport, LengthStats, FilterAdapter,
+                   JsonLinesAdapter, HitRecord};
+pub use channel::{ChannelAdapter, HitReceiver, Backpressure, hit_channel};
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use bincode;
+use chrono::prelude::*;
+use flate2::Crc;
+use libc;
+
+use mdbm::{Chunk, ReadChunk, MdbmError, FORMAT_VERSION};
+
+const MAGIC: &[u8; 8] = b"MDBMLOG2";
+const PUT: u8 = 1;
+const DELETE: u8 = 2;
+/// A record header: the record type, key length and value length, followed
+/// by a checksum of those fields.
+const HEADER_LEN: usize = 13;
+const CHECKSUM_LEN: usize = 4;
+
+/// The offset and length of the latest value for each key.
+type Index = HashMap<Vec<u8>, (u64, usize)>;
+
+/// A chunk stored as an append-only log, with an in-memory index.
+///
+/// The file starts with a magic number and the chunk's creation date,
+/// followed by a sequence of records, each of which sets or deletes a key.
+/// The index maps each key to the position of its latest value, and is
+/// rebuilt by reading the log when the chunk is opened.
+///
+/// Values are encoded with bincode, as they are by gdbm, so buckets can be
+/// copied between the two formats without decoding them.
+///
+/// If the last record was only partially written, say because the process
+/// was killed, it is discarded on opening. Record headers are checksummed,
+/// so a corrupt length elsewhere in the log is an error rather than being
+/// mistaken for the end of the log.
+///
+/// A chunk opened for writing holds an exclusive lock on its file, so that
+/// only one process writes to it. Chunks opened with `open_reader` are read
+/// as they are, and may be read while another process writes to them.
+pub struct LogChunk {
+    file: File,
+    index: Index,
+    /// The end of the last complete record.
+    len: u64,
+}
+
+impl LogChunk {
+    /// Appends a value that has already been encoded.
+    pub fn store_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<(), MdbmError> {
+        let offset = self.append(PUT, key, value)?;
+        self.index.insert(key.to_owned(), (offset, value.len()));
+        Ok(())
+    }
+
+    /// Writes a record, returning the offset of its value.
+    fn append(&mut self, kind: u8, key: &[u8], value: &[u8]) -> Result<u64, MdbmError> {
+        let mut record = bincode::serialize(&(kind, key.len() as u32, value.len() as u32))?;
+        let checksum = header_checksum(&record);
+        record.extend(bincode::serialize(&checksum)?);
+        debug_assert_eq!(record.len(), HEADER_LEN);
+        record.extend_from_slice(key);
+        record.extend_from_slice(value);
+        self.file.seek(SeekFrom::Start(self.len))?;
+        // a single write, so that a crash leaves at most one partial record
+        self.file.write_all(&record)?;
+        let value_offset = self.len + (HEADER_LEN + key.len()) as u64;
+        self.len += record.len() as u64;
+        Ok(value_offset)
+    }
+}
+
+/// Reads the records from `offset` to `file_len`, returning the index and
+/// the end of the last complete record.
+fn read_index<R: Read>(mut reader: R, mut offset: u64, file_len: u64)
+    -> Result<(Index, u64), MdbmError>
+{
+    let mut index = Index::new();
+    let mut header = [0; HEADER_LEN];
+    loop {
+        if !read_or_eof(&mut reader, &mut header)? { break }
+        let (kind, key_len, value_len, checksum): (u8, u32, u32, u32) =
+            bincode::deserialize(&header)?;
+        if checksum != header_checksum(&header[..HEADER_LEN - CHECKSUM_LEN]) {
+            // a crash can leave zeroes where the last record should be
+            if header.iter().all(|&b| b == 0) && zeroed_to_end(&mut reader)? { break }
+            return Err(invalid_data("corrupt record header").into());
+        }
+        let record_len = (HEADER_LEN as u64) + key_len as u64 + value_len as u64;
+        // the lengths are intact, so a record which runs past the end of
+        // the file can only be the last one, partially written
+        if offset + record_len > file_len { break }
+        let mut key = vec![0; key_len as usize];
+        reader.read_exact(&mut key)?;
+        let value_offset = offset + (HEADER_LEN + key.len()) as u64;
+        let mut value = vec![0; value_len as usize];
+        reader.read_exact(&mut value)?;
+        match kind {
+            PUT => { index.insert(key, (value_offset, value.len())); }
+            DELETE => { index.remove(&key); }
+            _ => return Err(invalid_data("unknown record type").into()),
+        }
+        offset = value_offset + value.len() as u64;
+    }
+    Ok((index, offset))
+}
+
+/// Fills `buf`, returning `false` if the end of the file is reached first.
+pub fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads to the end of the file, returning whether every byte is zero.
+pub fn zeroed_to_end<R: Read>(reader: &mut R) -> io::Result<bool> {
+    let mut buf = [0; 4096];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(true),
+            n => if buf[..n].iter().any(|&b| b != 0) { return Ok(false) },
+        }
+    }
+}
+
+fn header_checksum(fields: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(fields);
+    crc.sum()
+}
+
+/// Locks `file` for writing, failing if another process has it open.
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl LogChunk {
+    /// Reads the creation date and index of a chunk, returning them along
+    /// with the length of the file.
+    fn load(file: File) -> Result<(Self, DateTime<Utc>, u64), MdbmError> {
+        let (created_at, header_len, file_len) = {
+            let mut reader = BufReader::new(&file);
+            let mut magic = [0; 8];
+            reader.read_exact(&mut magic)?;
+            if &magic != MAGIC {
+                return Err(invalid_data("not a log chunk").into());
+            }
+            let created_at: DateTime<Utc> = bincode::deserialize_from(&mut reader)?;
+            let header_len = MAGIC.len() as u64 + bincode::serialized_size(&created_at)?;
+            (created_at, header_len, file.metadata()?.len())
+        };
+
+        let (index, len) = {
+            let mut file = &file;
+            file.seek(SeekFrom::Start(header_len))?;
+            read_index(BufReader::new(file), header_len, file_len)?
+        };
+        Ok((LogChunk { file, index, len }, created_at, file_len))
+    }
+}
+
+impl Chunk for LogChunk {
+    const EXTENSION: &'static str = "log";
+    type Reader = LogChunk;
+
+    fn open(path: &Path) -> Result<(Self, DateTime<Utc>), MdbmError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        lock_exclusive(&file)?;
+        let (chunk, created_at, file_len) = LogChunk::load(file)?;
+        if chunk.len < file_len {
+            eprintln!("discarding partial record in {:?}", path);
+            chunk.file.set_len(chunk.len)?;
+        }
+        Ok((chunk, created_at))
+    }
+
+    /// A partial record at the end of the file is ignored, but left in place.
+    fn open_reader(path: &Path) -> Result<(Self, DateTime<Utc>), MdbmError> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let (chunk, created_at, _) = LogChunk::load(file)?;
+        Ok((chunk, created_at))
+    }
+
+    fn create(path: &Path, created_at: DateTime<Utc>) -> Result<Self, MdbmError> {
+        let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+        lock_exclusive(&file)?;
+        let mut header = MAGIC.to_vec();
+        header.extend(bincode::serialize(&created_at)?);
+        file.write_all(&header)?;
+        Ok(LogChunk { file, index: HashMap::new(), len: header.len() as u64 })
+    }
+
+    fn store<V: Serialize>(&mut self, key: &[u8], value: &V) -> Result<(), MdbmError> {
+        let value = bincode::serialize(value)?;
+        self.store_bytes(key, &value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), MdbmError> {
+        if self.index.remove(key).is_some() {
+            self.append(DELETE, key, &[])?;
+        }
+        Ok(())
+    }
+    fn sync(&mut self) -> Result<(), MdbmError> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl ReadChunk for LogChunk {
+    fn fetch<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>, MdbmError> {
+        let (offset, len) = match self.index.get(key) {
+            Some(&entry) => entry,
+            None => return Ok(None),
+        };
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut value = vec![0; len];
+        file.read_exact(&mut value)?;
+        Ok(Some(bincode::deserialize(&value)?))
+    }
+
+    fn count(&self) -> Result<usize, MdbmError> {
+        Ok(self.index.len())
+    }
+
+    fn keys(&self) -> Result<Vec<Vec<u8>>, MdbmError> {
+        Ok(self.index.keys().cloned().collect())
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool, MdbmError> {
+        Ok(self.index.contains_key(key))
+    }
+
+    /// Log chunks have always held buckets.
+    fn version(&self) -> Result<u32, MdbmError> {
+        Ok(FORMAT_VERSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn log_chunk() {
+        let tempdir = TempDir::new("logchunk_test").unwrap();
+        let path = tempdir.path().join("chunk.log");
+        let created_at = Utc::now();
+        {
+            let mut chunk = LogChunk::create(&path, created_at).unwrap();
+            chunk.store(b"one", &vec![1]).unwrap();
+            chunk.store(b"two", &vec![2]).unwrap();
+            chunk.store(b"one", &vec![1, 11]).unwrap();
+            chunk.remove(b"two").unwrap();
+            chunk.store(b"three", &vec![3]).unwrap();
+            assert_eq!(chunk.fetch::<Vec<i32>>(b"one").unwrap(), Some(vec![1, 11]));
+        }
+
+        // simulate a crash partway through writing a record
+        let full_len = path.metadata().unwrap().len();
+        OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(&[PUT, 4, 0, 0]).unwrap();
+
+        let (chunk, date) = LogChunk::open(&path).unwrap();
+        assert_eq!(date, created_at);
+        assert_eq!(chunk.count().unwrap(), 2);
+        assert_eq!(chunk.fetch::<Vec<i32>>(b"one").unwrap(), Some(vec![1, 11]));
+        assert_eq!(chunk.fetch::<Vec<i32>>(b"two").unwrap(), None);
+        assert_eq!(chunk.fetch::<Vec<i32>>(b"three").unwrap(), Some(vec![3]));
+        assert_eq!(path.metadata().unwrap().len(), full_len);
+    }
+
+    #[test]
+    fn corrupt_length() {
+        let tempdir = TempDir::new("logchunk_test").unwrap();
+        let path = tempdir.path().join("chunk.log");
+        let first_record = {
+            let mut chunk = LogChunk::create(&path, Utc::now()).unwrap();
+            let first_record = chunk.len;
+            chunk.store(b"one", &vec![1]).unwrap();
+            chunk.store(b"two", &vec![2]).unwrap();
+            first_record
+        };
+        let len = path.metadata().unwrap().len();
+
+        // a key length which runs past the end of the file
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(first_record + 1)).unwrap();
+        file.write_all(&[0xff]).unwrap();
+        drop(file);
+        match LogChunk::open(&path) {
+            Err(MdbmError::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            Err(e) => panic!("expected invalid data, found {:?}", e),
+            Ok(_) => panic!("expected invalid data"),
+        }
+        assert_eq!(path.metadata().unwrap().len(), len);
+    }
+
+    #[test]
+    fn zeroed_tail() {
+        let tempdir = TempDir::new("logchunk_test").unwrap();
+        let path = tempdir.path().join("chunk.log");
+        {
+            let mut chunk = LogChunk::create(&path, Utc::now()).unwrap();
+            chunk.store(b"one", &vec![1]).unwrap();
+        }
+        let len = path.metadata().unwrap().len();
+
+        // a crash can leave the end of the file zeroed
+        OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(&[0; 40]).unwrap();
+        {
+            let (chunk, _) = LogChunk::open(&path).unwrap();
+            assert_eq!(chunk.fetch::<Vec<i32>>(b"one").unwrap(), Some(vec![1]));
+        }
+        assert_eq!(path.metadata().unwrap().len(), len);
+
+        // but zeroes followed by anything else are corrupt
+        OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+        assert!(LogChunk::open(&path).is_err());
+    }
+
+    #[test]
+    fn exclusive() {
+        let tempdir = TempDir::new("logchunk_test").unwrap();
+        let path = tempdir.path().join("chunk.log");
+        let chunk = LogChunk::create(&path, Utc::now()).unwrap();
+        assert!(LogChunk::open(&path).is_err());
+        drop(chunk);
+        assert!(LogChunk::open(&path).is_ok());
+    }
+
+    #[test]
+    fn not_a_log() {
+        let tempdir = TempDir::new("logchunk_test").unwrap();
+        let path = tempdir.path().join("chunk.log");
+        File::create(&path).unwrap().write_all(b"garbage!garbage!").unwrap();
+        assert!(LogChunk::open(&path).is_err());
+    }
+}
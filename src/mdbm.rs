@@ -1,171 +1,1095 @@
-
-use std::fs::DirBuilder;
+use std::collections::HashSet;
+use std::fs::{self, DirBuilder};
 use std::path::{Path, PathBuf};
-use std::collections::BTreeMap;
-use std::io;
-use std::ffi::OsStr;
-use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::slice;
+use std::str;
+use std::vec;
+use std::ffi::{OsStr, OsString};
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
 use std::ops::Drop;
+use std::time::Duration;
 
 use serde::Serialize;
 use serde::de::DeserializeOwned;
+use bincode;
+use serde_json;
 use chrono::prelude::*;
-use gnudbm::{GdbmOpener, RwHandle};
+use gnudbm::{self, GdbmOpener, GdbmError, ReadHandle, RwHandle};
 use lru_cache::LruCache;
 
-use anagrams::Store;
+use anagrams::{Store, TryStore, BucketPolicy};
+use logchunk::LogChunk;
+use wal::{WriteAheadLog, Buckets};
 
 // in # of items
 const CACHE_SIZE: usize = 200_000;
 const DB_CREATION_DATE_KEY: &str = "net.cmyr.creationDate";
+const DB_FORMAT_VERSION_KEY: &str = "net.cmyr.formatVersion";
+/// The format of new chunks, in which each key maps to a `Vec<V>`.
+///
+/// Chunks written before buckets were introduced have no version, and hold
+/// a single `V` per key; they are read as version 1, with each value
+/// treated as a bucket of one.
+pub const FORMAT_VERSION: u32 = 2;
+const QUARANTINE_EXTENSION: &str = "corrupt";
+const WAL_FILE_NAME: &str = "cache.wal";
+/// The cache is written to disk once its write-ahead log reaches this size.
+const WAL_CHECKPOINT_BYTES: u64 = 64 * 1024 * 1024;
+/// gdbm error codes which mean a file isn't a readable database, such as
+/// a bad magic number or a missing creation date.
+const GDBM_FORMAT_ERRORS: &[u32] = &[7, GDBM_ITEM_NOT_FOUND, 18, 21, 22, 25, 31];
+const GDBM_ITEM_NOT_FOUND: u32 = 15;
 
-/// Wraps a collection of gdbm files, allowing chunks to be dropped
+/// Wraps a collection of on-disk chunks, allowing chunks to be dropped
 /// as necessary.
-pub struct Mdbm<V: Serialize> {
+///
+/// Each key maps to a bucket of values, stored as a single `Vec<V>`.
+/// A bucket may be stored in more than one chunk, if it changed after it
+/// was first written; the copy in the newest chunk is the one that is used.
+/// Recently used buckets are cached in memory, and written to the newest
+/// chunk when they are evicted from the cache, or when the store is dropped.
+/// Every change to the cache is also appended to a write-ahead log, which
+/// is replayed on opening, so the cache survives the process being killed.
+/// The log isn't synced to disk on each change, so changes since the last
+/// checkpoint may still be lost if the machine itself goes down.
+///
+/// Chunks which can't be read are quarantined: they are renamed with a
+/// `.corrupt` extension, and skipped from then on.
+pub struct ChunkedStore<V: Serialize, C: Chunk> {
     base_path: PathBuf,
-    cache: RefCell<LruCache<Vec<u8>, V>>,
+    cache: RefCell<LruCache<Vec<u8>, Vec<V>>>,
+    /// Cached buckets which have changed since they were last written.
+    dirty: HashSet<Vec<u8>>,
     chunk_size: usize,
-    chunks: Vec<RwHandle>,
+    chunks: Vec<ChunkFile<C>>,
     last_chunk_len: usize,
+    policy: BucketPolicy,
+    retention: RetentionPolicy,
+    quarantined: Vec<PathBuf>,
+    wal: WriteAheadLog,
+}
+
+/// A `ChunkedStore` of gdbm files.
+pub type Mdbm<V> = ChunkedStore<V, GdbmChunk>;
+
+/// A `ChunkedStore` of append-only log files, which doesn't depend on gdbm.
+pub type LogStore<V> = ChunkedStore<V, LogChunk>;
+
+/// Read access to a chunk.
+pub trait ReadChunk {
+    fn fetch<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>, MdbmError>;
+    /// The number of keys in the chunk.
+    fn count(&self) -> Result<usize, MdbmError>;
+    /// Every key in the chunk, in no particular order.
+    fn keys(&self) -> Result<Vec<Vec<u8>>, MdbmError>;
+    fn contains_key(&self, key: &[u8]) -> Result<bool, MdbmError>;
+    /// The format of the values in the chunk; see `FORMAT_VERSION`.
+    fn version(&self) -> Result<u32, MdbmError>;
+}
+
+/// The file format of the chunks in a `ChunkedStore`.
+pub trait Chunk: ReadChunk + Sized {
+    /// The file extension of chunks in this format.
+    const EXTENSION: &'static str;
+    /// A chunk opened with `open_reader`.
+    type Reader: ReadChunk;
+    /// Opens an existing chunk, returning it and its creation date.
+    fn open(path: &Path) -> Result<(Self, DateTime<Utc>), MdbmError>;
+    /// Opens an existing chunk for reading only. The file is neither locked
+    /// nor changed, so it can be read while another process writes to it.
+    fn open_reader(path: &Path) -> Result<(Self::Reader, DateTime<Utc>), MdbmError>;
+    fn create(path: &Path, created_at: DateTime<Utc>) -> Result<Self, MdbmError>;
+    fn store<V: Serialize>(&mut self, key: &[u8], value: &V) -> Result<(), MdbmError>;
+    fn remove(&mut self, key: &[u8]) -> Result<(), MdbmError>;
+    /// Flushes any writes to disk.
+    fn sync(&mut self) -> Result<(), MdbmError>;
+}
+
+/// Information about a single chunk of a `ChunkedStore`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChunkStats {
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    /// The number of keys in the chunk.
+    pub entries: usize,
+    /// The size of the chunk on disk.
+    pub bytes: u64,
+}
+
+/// A bucket, as written by `ChunkedStore::export_jsonl`. The key is
+/// hex encoded.
+#[derive(Serialize, Deserialize)]
+struct ExportRecord<B> {
+    key: String,
+    values: B,
+}
+
+/// Limits on the chunks kept by an `Mdbm`.
+///
+/// When a limit is exceeded the oldest chunks are closed, and deleted or
+/// moved to `archive_dir`. The newest chunk is always kept.
+///
+/// Cached buckets which aren't in any remaining chunk are dropped along
+/// with their chunk, including any candidates added since they were read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_chunks: Option<usize>,
+    /// The most bytes the chunk files may take on disk, in total.
+    pub max_bytes: Option<u64>,
+    /// Chunks created longer ago than this are dropped.
+    pub max_age: Option<Duration>,
+    /// If set, dropped chunks are moved here instead of being deleted.
+    pub archive_dir: Option<PathBuf>,
+}
+
+/// A single open chunk.
+struct ChunkFile<C> {
+    path: PathBuf,
+    created_at: DateTime<Utc>,
+    chunk: C,
+    version: u32,
+    /// Set when a read fails; the chunk is quarantined before the next write.
+    corrupt: Cell<bool>,
+}
+
+/// A chunk stored as a gdbm file.
+pub struct GdbmChunk(RwHandle);
+
+/// A gdbm chunk opened for reading, without taking gdbm's lock.
+pub struct GdbmReader(ReadHandle);
+
+/// An error from an `Mdbm`.
+#[derive(Debug)]
+pub enum MdbmError {
+    Io(io::Error),
+    Gdbm(gnudbm::Error),
+    Bincode(bincode::Error),
+    Json(serde_json::Error),
+    /// A chunk's contents could not be decoded. It is quarantined, and
+    /// skipped from then on.
+    Corrupt { path: PathBuf, error: Box<MdbmError> },
+    /// A chunk was written in a newer format than this version understands.
+    UnsupportedVersion(u32),
+    /// The write-ahead log at this path has changes which aren't in any
+    /// chunk yet. Opening and closing the store writes them.
+    UnrecoveredLog(PathBuf),
+}
+
+impl fmt::Display for MdbmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MdbmError::Io(ref e) => write!(f, "io error: {}", e),
+            MdbmError::Gdbm(ref e) => write!(f, "gdbm error: {}", e),
+            MdbmError::Bincode(ref e) => write!(f, "encoding error: {}", e),
+            MdbmError::Json(ref e) => write!(f, "json error: {}", e),
+            MdbmError::Corrupt { ref path, ref error } =>
+                write!(f, "corrupt chunk {}: {}", path.display(), error),
+            MdbmError::UnsupportedVersion(version) =>
+                write!(f, "unsupported chunk format version {}", version),
+            MdbmError::UnrecoveredLog(ref path) =>
+                write!(f, "{} has unrecovered changes; open and close the store first",
+                       path.display()),
+        }
+    }
+}
+
+impl Error for MdbmError {}
+
+impl MdbmError {
+    /// Whether the error means a chunk's contents can't be read, as
+    /// opposed to the file being locked or otherwise inaccessible.
+    fn is_format_error(&self) -> bool {
+        match *self {
+            MdbmError::Bincode(_) => true,
+            MdbmError::Io(ref e) => e.kind() == io::ErrorKind::InvalidData
+                || e.kind() == io::ErrorKind::UnexpectedEof,
+            MdbmError::Gdbm(ref e) => match *e {
+                gnudbm::Error::NoRecord | gnudbm::Error::Bincode(_) => true,
+                gnudbm::Error::Internal(GdbmError::Other(code)) =>
+                    GDBM_FORMAT_ERRORS.contains(&code),
+                gnudbm::Error::Internal(_) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl From<io::Error> for MdbmError {
+    fn from(err: io::Error) -> MdbmError {
+        MdbmError::Io(err)
+    }
+}
+
+impl From<gnudbm::Error> for MdbmError {
+    fn from(err: gnudbm::Error) -> MdbmError {
+        MdbmError::Gdbm(err)
+    }
+}
+
+impl From<bincode::Error> for MdbmError {
+    fn from(err: bincode::Error) -> MdbmError {
+        MdbmError::Bincode(err)
+    }
+}
+
+impl From<serde_json::Error> for MdbmError {
+    fn from(err: serde_json::Error) -> MdbmError {
+        MdbmError::Json(err)
+    }
+}
+
+impl Chunk for GdbmChunk {
+    const EXTENSION: &'static str = "dbm";
+    type Reader = GdbmReader;
+
+    fn open(path: &Path) -> Result<(Self, DateTime<Utc>), MdbmError> {
+        let db = GdbmOpener::new().readwrite(path)?;
+        let created_at = {
+            let created_at = db.fetch(DB_CREATION_DATE_KEY.as_bytes())?;
+            created_at.deserialize()?
+        };
+        Ok((GdbmChunk(db), created_at))
+    }
+
+    fn open_reader(path: &Path) -> Result<(GdbmReader, DateTime<Utc>), MdbmError> {
+        let db = GdbmOpener::new().no_lock(true).readonly(path)?;
+        let created_at = {
+            let created_at = db.fetch(DB_CREATION_DATE_KEY.as_bytes())?;
+            created_at.deserialize()?
+        };
+        Ok((GdbmReader(db), created_at))
+    }
+
+    fn create(path: &Path, created_at: DateTime<Utc>) -> Result<Self, MdbmError> {
+        let mut db = GdbmOpener::new()
+            .create(true)
+            .readwrite(path)?;
+        db.store(DB_CREATION_DATE_KEY.as_bytes(), &created_at)?;
+        db.store(DB_FORMAT_VERSION_KEY.as_bytes(), &FORMAT_VERSION)?;
+        Ok(GdbmChunk(db))
+    }
+
+    fn store<V: Serialize>(&mut self, key: &[u8], value: &V) -> Result<(), MdbmError> {
+        self.0.store(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), MdbmError> {
+        self.0.remove(key)?;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), MdbmError> {
+        self.0.sync();
+        Ok(())
+    }
+}
+
+impl ReadChunk for GdbmChunk {
+    fn fetch<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>, MdbmError> {
+        match self.0.fetch(key) {
+            Ok(val) => Ok(Some(val.deserialize()?)),
+            Err(ref e) if e.is_no_record() => Ok(None),
+            // gnudbm doesn't always report a missing key as `NoRecord`
+            Err(_) if !self.0.contains_key(key)? => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn count(&self) -> Result<usize, MdbmError> {
+        // don't count the creation date or format version
+        let metadata = if self.0.contains_key(DB_FORMAT_VERSION_KEY.as_bytes())? { 2 } else { 1 };
+        Ok(self.0.count()?.saturating_sub(metadata))
+    }
+
+    fn keys(&self) -> Result<Vec<Vec<u8>>, MdbmError> {
+        let keys = self.0.iter()
+            .map(|(key, _)| key.as_bytes().to_owned())
+            .filter(|key| !is_metadata_key(key))
+            .collect();
+        Ok(keys)
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool, MdbmError> {
+        Ok(self.0.contains_key(key)?)
+    }
+
+    fn version(&self) -> Result<u32, MdbmError> {
+        Ok(self.fetch(DB_FORMAT_VERSION_KEY.as_bytes())?.unwrap_or(1))
+    }
+}
+
+impl ReadChunk for GdbmReader {
+    fn fetch<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>, MdbmError> {
+        match self.0.fetch(key) {
+            Ok(val) => Ok(Some(val.deserialize()?)),
+            Err(ref e) if is_missing(e) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn count(&self) -> Result<usize, MdbmError> {
+        // don't count the creation date or format version
+        let metadata = if self.contains_key(DB_FORMAT_VERSION_KEY.as_bytes())? { 2 } else { 1 };
+        Ok(self.0.count()?.saturating_sub(metadata))
+    }
+
+    fn keys(&self) -> Result<Vec<Vec<u8>>, MdbmError> {
+        let keys = self.0.iter()
+            .map(|(key, _)| key.as_bytes().to_owned())
+            .filter(|key| !is_metadata_key(key))
+            .collect();
+        Ok(keys)
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool, MdbmError> {
+        match self.0.fetch(key) {
+            Ok(_) => Ok(true),
+            Err(ref e) if is_missing(e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn version(&self) -> Result<u32, MdbmError> {
+        Ok(self.fetch(DB_FORMAT_VERSION_KEY.as_bytes())?.unwrap_or(1))
+    }
+}
+
+/// Whether a gdbm error means that a key wasn't found.
+fn is_missing(error: &gnudbm::Error) -> bool {
+    match *error {
+        gnudbm::Error::NoRecord => true,
+        gnudbm::Error::Internal(GdbmError::Other(code)) => code == GDBM_ITEM_NOT_FOUND,
+        _ => false,
+    }
+}
+
+fn is_metadata_key(key: &[u8]) -> bool {
+    key == DB_CREATION_DATE_KEY.as_bytes() || key == DB_FORMAT_VERSION_KEY.as_bytes()
+}
+
+impl<C: Chunk> ChunkFile<C> {
+    fn open(path: PathBuf) -> Result<Self, MdbmError> {
+        let (chunk, created_at) = C::open(&path)?;
+        ChunkFile::new(path, created_at, chunk)
+    }
+
+    fn create(path: PathBuf, created_at: DateTime<Utc>) -> Result<Self, MdbmError> {
+        let chunk = C::create(&path, created_at)?;
+        Ok(ChunkFile { path, created_at, chunk, version: FORMAT_VERSION, corrupt: Cell::new(false) })
+    }
+}
+
+impl<C: ReadChunk> ChunkFile<C> {
+    /// Wraps an opened chunk, checking that its format is supported.
+    fn new(path: PathBuf, created_at: DateTime<Utc>, chunk: C) -> Result<Self, MdbmError> {
+        let version = chunk.version()?;
+        if version > FORMAT_VERSION {
+            return Err(MdbmError::UnsupportedVersion(version));
+        }
+        Ok(ChunkFile { path, created_at, chunk, version, corrupt: Cell::new(false) })
+    }
+
+    /// Reads the bucket for `key`. Version 1 chunks hold a single value,
+    /// which is read as a bucket of one.
+    fn fetch_bucket<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<Vec<V>>, MdbmError> {
+        if self.version == 1 {
+            return Ok(self.chunk.fetch::<V>(key)?.map(|value| vec![value]));
+        }
+        self.chunk.fetch(key)
+    }
 }
 
-impl<V: Serialize> Mdbm<V> {
+impl<V: Serialize, C: Chunk> ChunkedStore<V, C> {
     /// Loads or creates a new db collection.
-    pub fn new<P: AsRef<Path>>(p: P, chunk_size: usize) -> Self {
+    ///
+    /// # Panics
+    ///
+    /// Panics if the collection cannot be opened. See `Mdbm::open`.
+    pub fn new<P: AsRef<Path>>(p: P, chunk_size: usize) -> Self
+        where V: DeserializeOwned,
+    {
+        ChunkedStore::with_policy(p, chunk_size, BucketPolicy::default())
+    }
+
+    /// Loads or creates a new db collection, with the given bucket policy.
+    pub fn with_policy<P: AsRef<Path>>(p: P, chunk_size: usize, policy: BucketPolicy) -> Self
+        where V: DeserializeOwned,
+    {
+        ChunkedStore::open_with_policy(p, chunk_size, policy)
+            .expect("failed to open mdbm")
+    }
+
+    /// Loads or creates a new db collection, returning an error if the
+    /// directory can't be read or created.
+    ///
+    /// Chunks that can't be decoded are quarantined rather than treated
+    /// as errors; see `Mdbm::quarantined`. Chunks that can't be opened for
+    /// other reasons, such as being locked by another process, are errors.
+    pub fn open<P: AsRef<Path>>(p: P, chunk_size: usize) -> Result<Self, MdbmError>
+        where V: DeserializeOwned,
+    {
+        ChunkedStore::open_with_policy(p, chunk_size, BucketPolicy::default())
+    }
+
+    /// Any buckets recovered from the write-ahead log are written to the
+    /// newest chunk.
+    pub fn open_with_policy<P: AsRef<Path>>(p: P, chunk_size: usize, policy: BucketPolicy)
+        -> Result<Self, MdbmError>
+        where V: DeserializeOwned,
+    {
         let base_path = p.as_ref().to_owned();
         eprintln!("using base path {}", base_path.display());
         if !base_path.exists() {
             DirBuilder::new()
                 .recursive(true)
-                .create(&base_path)
-                .expect("creating mdbm directory failed");
-        }
-
-        let mut chunks = BTreeMap::new();
-        for fp in iter_dbm_paths(&base_path).expect("failed to load dbm chunks") {
-            let mut db = GdbmOpener::new()
-                .readwrite(&fp)
-                .expect("failed to load db");
-            let created_at = {
-                let created_at = db.fetch(DB_CREATION_DATE_KEY.as_bytes()).unwrap();
-                let created_at: DateTime<Utc> = created_at.deserialize()
-                    .expect(&format!("failed to parse created at date for path {:?}", fp));
-                created_at
-            };
-            chunks.insert(created_at, db);
-        }
-
-        // this is gross b/c BTreeMap doesn't implement drain; we just used it to sort.
-        let keys = chunks.keys().cloned().collect::<Vec<_>>();
-        let mut vals = Vec::new();
-        for key in keys {
-            vals.push(chunks.remove(&key).unwrap());
+                .create(&base_path)?;
+        }
+
+        let mut chunks: Vec<ChunkFile<C>> = Vec::new();
+        let mut quarantined = Vec::new();
+        for fp in iter_chunk_paths(&base_path, C::EXTENSION)? {
+            match ChunkFile::open(fp.clone()) {
+                Ok(chunk) => chunks.push(chunk),
+                Err(ref e) if e.is_format_error() => {
+                    eprintln!("failed to open chunk {:?}: {}", fp, e);
+                    quarantined.push(quarantine(&fp)?);
+                }
+                Err(e) => return Err(e),
+            }
         }
+        sort_chunks(&mut chunks);
 
-        let chunks = vals;
         let cache = RefCell::new(LruCache::new(CACHE_SIZE));
-        let last_chunk_len = chunks.last().map(|c| c.count().unwrap())
-            .unwrap_or(0);
+        let last_chunk_len = match chunks.last() {
+            Some(chunk) => chunk.chunk.count()?,
+            None => 0,
+        };
+
+        let (wal, recovered) = WriteAheadLog::open(&base_path.join(WAL_FILE_NAME))?;
+        let mut store = ChunkedStore {
+            base_path, cache, chunk_size, chunks, last_chunk_len, policy,
+            dirty: HashSet::new(),
+            retention: RetentionPolicy::default(),
+            quarantined, wal,
+        };
+        store.recover(recovered)?;
+        Ok(store)
+    }
+
+    /// Sets the limits on which chunks are kept. These are enforced
+    /// whenever a chunk is added, or by calling `enforce_retention`.
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Drops the oldest chunks until the retention policy is satisfied,
+    /// returning the paths of the dropped chunks.
+    pub fn enforce_retention(&mut self) -> Result<Vec<PathBuf>, MdbmError> {
+        let mut n_expired = 0;
+        if let Some(max) = self.retention.max_chunks {
+            n_expired = self.chunks.len().saturating_sub(max);
+        }
+        // an age too large to represent can never be exceeded
+        let max_age = self.retention.max_age
+            .and_then(|age| ::chrono::Duration::from_std(age).ok());
+        if let Some(max_age) = max_age {
+            let now = Utc::now();
+            let n_old = self.chunks.iter()
+                .take_while(|c| now.signed_duration_since(c.created_at) > max_age)
+                .count();
+            n_expired = n_expired.max(n_old);
+        }
+        if let Some(max_bytes) = self.retention.max_bytes {
+            let sizes = self.chunks.iter()
+                .map(|c| c.path.metadata().map(|m| m.len()))
+                .collect::<io::Result<Vec<_>>>()?;
+            let mut total: u64 = sizes.iter().sum();
+            let mut n_large = 0;
+            for size in sizes {
+                if total <= max_bytes { break }
+                total -= size;
+                n_large += 1;
+            }
+            n_expired = n_expired.max(n_large);
+        }
+
+        // always keep the chunk we're writing to
+        n_expired = n_expired.min(self.chunks.len().saturating_sub(1));
+        let expired = self.chunks.drain(..n_expired).collect::<Vec<_>>();
+        self.uncache_expired(&expired)?;
+        let mut dropped = Vec::new();
+        for chunk in expired {
+            let path = chunk.path.clone();
+            drop(chunk);
+            match self.retention.archive_dir {
+                Some(ref dir) => {
+                    DirBuilder::new().recursive(true).create(dir)?;
+                    let name = path.file_name().expect("chunk paths have file names");
+                    fs::rename(&path, dir.join(name))?;
+                }
+                None => fs::remove_file(&path)?,
+            }
+            eprintln!("dropped chunk {:?}", path);
+            dropped.push(path);
+        }
+        Ok(dropped)
+    }
+
+    /// Removes cached buckets which are only in `expired` chunks, so that
+    /// they aren't written back to the newest chunk.
+    fn uncache_expired(&mut self, expired: &[ChunkFile<C>]) -> Result<(), MdbmError> {
+        if expired.is_empty() { return Ok(()) }
+        let keys = self.cache.borrow().iter().map(|(k, _)| k.clone()).collect::<Vec<_>>();
+        for key in keys {
+            if !contains_key(expired, &key)? || contains_key(&self.chunks, &key)? { continue }
+            // so that recovering from a crash doesn't restore it either
+            self.wal.append::<V>(&key, None)?;
+            self.cache.borrow_mut().remove(&key);
+            self.dirty.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of keys in each chunk, and its size, oldest first.
+    pub fn chunk_stats(&self) -> Result<Vec<ChunkStats>, MdbmError> {
+        chunk_stats(&self.chunks)
+    }
 
-        Mdbm { base_path, cache, chunk_size, chunks, last_chunk_len }
+    /// Returns every bucket in the store, including those only in the cache.
+    ///
+    /// When a key is in several chunks, only the newest bucket is returned.
+    /// Reading the chunks doesn't affect the cache.
+    pub fn entries<'a>(&'a self) -> Entries<'a, V, C>
+        where V: DeserializeOwned + Clone,
+    {
+        let cached = self.cache.borrow().iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<Vec<_>>();
+        Entries {
+            cached: cached.into_iter(),
+            chunks: self.chunks.iter(),
+            current: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Writes every bucket as a line of JSON, returning the number written.
+    pub fn export_jsonl<W: Write>(&self, mut writer: W) -> Result<usize, MdbmError>
+        where V: DeserializeOwned + Clone,
+    {
+        let mut count = 0;
+        for entry in self.entries() {
+            let (key, values) = entry?;
+            let record = ExportRecord { key: to_hex(&key), values: &values };
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
     }
 
-    fn add_chunk(&mut self) {
+    /// Inserts the buckets written by `export_jsonl`, returning the number
+    /// of buckets read. Values are added to any existing buckets, subject
+    /// to the bucket policy.
+    pub fn import_jsonl<R: BufRead>(&mut self, reader: R) -> Result<usize, MdbmError>
+        where V: DeserializeOwned + Clone,
+    {
+        let mut count = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() { continue }
+            let record: ExportRecord<Vec<V>> = serde_json::from_str(&line)?;
+            let key = from_hex(&record.key)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid key"))?;
+            for value in record.values {
+                self.try_insert(key.clone(), value)?;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The paths of chunks that have been quarantined since opening.
+    pub fn quarantined(&self) -> &[PathBuf] {
+        &self.quarantined
+    }
+
+    fn add_chunk(&mut self) -> Result<(), MdbmError> {
         let now: DateTime<Utc> = Utc::now();
-        let filename = now.format("%Y-%m-%d_%H_%M_%S.dbm").to_string();
-        eprintln!("adding chunk {}", filename);
-        let filepath = self.base_path.join(filename);
-        let mut db = GdbmOpener::new()
-            .create(true)
-            .readwrite(&filepath)
-            .expect(&format!("failed to create new gdbm section at {:?}", filepath));
-        db.store(DB_CREATION_DATE_KEY.as_bytes(), &now).unwrap();
-        self.chunks.push(db);
+        let stem = now.format("%Y-%m-%d_%H_%M_%S_%6f").to_string();
+        // chunks may be added more than once a second
+        let mut filepath = self.base_path.join(format!("{}.{}", stem, C::EXTENSION));
+        let mut n = 1;
+        while filepath.exists() {
+            filepath = self.base_path.join(format!("{}_{}.{}", stem, n, C::EXTENSION));
+            n += 1;
+        }
+        eprintln!("adding chunk {:?}", filepath.file_name().unwrap());
+        let chunk = ChunkFile::create(filepath, now)?;
+        self.chunks.push(chunk);
         self.last_chunk_len = 0;
+        self.enforce_retention()?;
+        Ok(())
+    }
+
+    /// Adds a chunk if there are none, or if the newest is in an old format,
+    /// so that the newest chunk can always be written to.
+    fn ensure_writable_chunk(&mut self) -> Result<(), MdbmError> {
+        let needs_chunk = match self.chunks.last() {
+            Some(chunk) => chunk.version != FORMAT_VERSION,
+            None => true,
+        };
+        if needs_chunk {
+            self.add_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Loads the bucket for `key` from disk, if it isn't already cached.
+    fn cache_bucket(&self, key: &[u8]) -> Result<(), MdbmError>
+        where V: DeserializeOwned,
+    {
+        if self.cache.borrow_mut().contains_key(key) { return Ok(()) }
+        // newer chunks take precedence
+        for chunk in self.chunks.iter().rev().filter(|c| !c.corrupt.get()) {
+            match chunk.fetch_bucket(key) {
+                Ok(Some(bucket)) => {
+                    self.cache.borrow_mut().insert(key.to_owned(), bucket);
+                    return Ok(())
+                }
+                Ok(None) => (),
+                // other errors, such as failing to read the file, may not last
+                Err(error) => {
+                    if !error.is_format_error() { return Err(error) }
+                    chunk.corrupt.set(true);
+                    let error = Box::new(error);
+                    return Err(MdbmError::Corrupt { path: chunk.path.clone(), error })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes and renames any chunks which have failed to read.
+    fn quarantine_corrupt(&mut self) -> Result<(), MdbmError> {
+        if !self.chunks.iter().any(|c| c.corrupt.get()) { return Ok(()) }
+        let (corrupt, chunks) = self.chunks.drain(..)
+            .partition::<Vec<_>, _>(|c| c.corrupt.get());
+        self.chunks = chunks;
+        for chunk in corrupt {
+            let path = chunk.path.clone();
+            // close the file before moving it
+            drop(chunk);
+            eprintln!("quarantining chunk {:?}", path);
+            self.quarantined.push(quarantine(&path)?);
+        }
+        self.last_chunk_len = match self.chunks.last() {
+            Some(chunk) => chunk.chunk.count()?,
+            None => 0,
+        };
+        Ok(())
+    }
+
+    /// Writes buckets recovered from the write-ahead log to the newest chunk.
+    fn recover(&mut self, buckets: Buckets<V>) -> Result<(), MdbmError> {
+        if buckets.is_empty() {
+            return Ok(());
+        }
+        eprintln!("recovering {} buckets from {}", buckets.len(), WAL_FILE_NAME);
+        self.ensure_writable_chunk()?;
+        // removed buckets were already removed from every chunk
+        for (key, bucket) in buckets.into_iter().filter_map(|(k, b)| b.map(|b| (k, b))) {
+            self.chunks.last_mut().unwrap().chunk.store(&key, &bucket)?;
+        }
+        self.chunks.last_mut().unwrap().chunk.sync()?;
+        self.last_chunk_len = self.chunks.last().unwrap().chunk.count()?;
+        self.wal.clear()?;
+        Ok(())
     }
 
-    fn check_health(&mut self)
+    /// Writes the changed buckets in the cache to the newest chunk, and
+    /// clears the write-ahead log.
+    fn checkpoint(&mut self) -> Result<(), MdbmError> {
+        {
+            let chunk = &mut self.chunks.last_mut().unwrap().chunk;
+            let mut cache = self.cache.borrow_mut();
+            for key in &self.dirty {
+                if let Some(bucket) = cache.get_mut(key) {
+                    chunk.store(key, bucket)?;
+                }
+            }
+            // the log is only cleared once the chunk is safely on disk
+            chunk.sync()?;
+        }
+        self.dirty.clear();
+        self.last_chunk_len = self.chunks.last().unwrap().chunk.count()?;
+        self.wal.clear()?;
+        Ok(())
+    }
+
+    /// Logs the new contents of a bucket, and then caches it. The cache is
+    /// only changed once the change has been logged.
+    fn update_bucket(&mut self, key: &[u8], bucket: Vec<V>) -> Result<(), MdbmError> {
+        self.wal.append(key, Some(&bucket))?;
+        self.cache.borrow_mut().insert(key.to_owned(), bucket);
+        self.dirty.insert(key.to_owned());
+        Ok(())
+    }
+
+    fn check_health(&mut self) -> Result<(), MdbmError>
         where V: Serialize,
     {
+        self.quarantine_corrupt()?;
+        self.ensure_writable_chunk()?;
+
         let cache_len = self.cache.borrow().len();
         if cache_len > (CACHE_SIZE / 10) * 9 {
             // clear out some cache space
             for _ in 0..(CACHE_SIZE / 10) {
                 let (k, v) = self.cache.borrow_mut().remove_lru().unwrap();
-                self.chunks.last_mut().unwrap().store(k, &v).unwrap();
+                if !self.dirty.contains(&k) { continue }
+                if let Err(e) = self.chunks.last_mut().unwrap().chunk.store(&k, &v) {
+                    // keep it, so that it's written later
+                    self.cache.borrow_mut().insert(k, v);
+                    return Err(e);
+                }
+                self.dirty.remove(&k);
             }
-            // can't ever be called before we add the first chunk
-            self.last_chunk_len = self.chunks.last().map(|c| c.count().unwrap())
-                .unwrap();
+            self.last_chunk_len = self.chunks.last().unwrap().chunk.count()?;
         }
-        let needs_chunk = self.chunks.last().is_none() ||
-            self.last_chunk_len >= self.chunk_size;
 
-        if needs_chunk {
-            self.add_chunk();
+        if self.wal.size() > WAL_CHECKPOINT_BYTES {
+            self.checkpoint()?;
+        }
+
+        if self.last_chunk_len >= self.chunk_size {
+            self.add_chunk()?;
         }
+        Ok(())
     }
 }
 
-impl<K, V> Store<K, V> for Mdbm<V>
+/// An iterator over the buckets in a `ChunkedStore`, newest first.
+///
+/// Returned by `ChunkedStore::entries`.
+pub struct Entries<'a, V, C: 'a> {
+    cached: vec::IntoIter<(Vec<u8>, Vec<V>)>,
+    /// The chunks that haven't been read yet.
+    chunks: slice::Iter<'a, ChunkFile<C>>,
+    /// The chunk being read, and its remaining keys.
+    current: Option<(&'a ChunkFile<C>, vec::IntoIter<Vec<u8>>)>,
+    seen: HashSet<Vec<u8>>,
+}
+
+impl<'a, V: DeserializeOwned, C: ReadChunk> Iterator for Entries<'a, V, C> {
+    type Item = Result<(Vec<u8>, Vec<V>), MdbmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((key, bucket)) = self.cached.next() {
+            self.seen.insert(key.clone());
+            return Some(Ok((key, bucket)));
+        }
+        loop {
+            if let Some((chunk, ref mut keys)) = self.current {
+                for key in keys {
+                    if self.seen.contains(&key) { continue }
+                    match chunk.fetch_bucket(&key) {
+                        Ok(Some(bucket)) => {
+                            self.seen.insert(key.clone());
+                            return Some(Ok((key, bucket)));
+                        }
+                        Ok(None) => (),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            }
+            // newer chunks take precedence
+            let chunk = self.chunks.next_back()?;
+            if chunk.corrupt.get() { continue }
+            match chunk.chunk.keys() {
+                Ok(keys) => self.current = Some((chunk, keys.into_iter())),
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes().chunks(2)
+        .map(|pair| match pair.len() {
+            2 => str::from_utf8(pair).ok().and_then(|p| u8::from_str_radix(p, 16).ok()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A read-only view of the chunks of a `ChunkedStore`, for inspecting a
+/// store which may be open in another process.
+///
+/// Chunks are opened with `Chunk::open_reader`, and nothing is written:
+/// chunks which can't be opened are skipped rather than quarantined, and
+/// the write-ahead log is left alone. Buckets which are only in another
+/// process's cache are not seen.
+pub struct StoreReader<V, C: Chunk> {
+    chunks: Vec<ChunkFile<C::Reader>>,
+    values: PhantomData<V>,
+}
+
+impl<V, C: Chunk> StoreReader<V, C> {
+    pub fn open<P: AsRef<Path>>(p: P) -> Result<Self, MdbmError> {
+        let mut chunks = Vec::new();
+        for fp in iter_chunk_paths(p.as_ref(), C::EXTENSION)? {
+            let opened = C::open_reader(&fp)
+                .and_then(|(chunk, created_at)| ChunkFile::new(fp.clone(), created_at, chunk));
+            match opened {
+                Ok(chunk) => chunks.push(chunk),
+                Err(e) => eprintln!("skipping chunk {:?}: {}", fp, e),
+            }
+        }
+        sort_chunks(&mut chunks);
+        Ok(StoreReader { chunks, values: PhantomData })
+    }
+
+    /// Returns the number of keys in each chunk, and its size, oldest first.
+    pub fn chunk_stats(&self) -> Result<Vec<ChunkStats>, MdbmError> {
+        chunk_stats(&self.chunks)
+    }
+
+    /// Returns every bucket in the chunks. When a key is in several chunks,
+    /// only the newest bucket is returned.
+    pub fn entries<'a>(&'a self) -> Entries<'a, V, C::Reader>
+        where V: DeserializeOwned,
+    {
+        Entries {
+            cached: Vec::new().into_iter(),
+            chunks: self.chunks.iter(),
+            current: None,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+/// Orders chunks oldest first.
+fn sort_chunks<C>(chunks: &mut [ChunkFile<C>]) {
+    chunks.sort_by(|a, b| (a.created_at, &a.path).cmp(&(b.created_at, &b.path)));
+}
+
+fn chunk_stats<C: ReadChunk>(chunks: &[ChunkFile<C>]) -> Result<Vec<ChunkStats>, MdbmError> {
+    chunks.iter()
+        .map(|c| Ok(ChunkStats {
+            path: c.path.clone(),
+            created_at: c.created_at,
+            entries: c.chunk.count()?,
+            bytes: c.path.metadata()?.len(),
+        }))
+        .collect()
+}
+
+/// Whether any of `chunks` holds `key`.
+fn contains_key<C: Chunk>(chunks: &[ChunkFile<C>], key: &[u8]) -> Result<bool, MdbmError> {
+    for chunk in chunks {
+        if chunk.chunk.contains_key(key)? { return Ok(true) }
+    }
+    Ok(false)
+}
+
+/// Renames a chunk so that it will no longer be loaded.
+fn quarantine(path: &Path) -> io::Result<PathBuf> {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(QUARANTINE_EXTENSION);
+    let new_path = PathBuf::from(name);
+    fs::rename(path, &new_path)?;
+    Ok(new_path)
+}
+
+impl<K, V, C> TryStore<K, V> for ChunkedStore<V, C>
     where K: AsRef<[u8]>,
           V: Serialize + DeserializeOwned + Clone,
+          C: Chunk,
 {
+    type Error = MdbmError;
 
-    fn remove(&mut self, key: &K) {
-        if self.cache.borrow_mut().remove(key.as_ref()).is_some() {
-            return
+    fn try_remove(&mut self, key: &K) -> Result<(), MdbmError> {
+        self.wal.append::<V>(key.as_ref(), None)?;
+        self.cache.borrow_mut().remove(key.as_ref());
+        self.dirty.remove(key.as_ref());
+        // older copies of the bucket may live in multiple chunks
+        for chunk in self.chunks.iter_mut().filter(|c| !c.corrupt.get()) {
+            chunk.chunk.remove(key.as_ref())?;
         }
-        for chunk in &self.chunks {
-            if let Ok(true) = chunk.remove(key.as_ref()) {
-                return
-            }
+        Ok(())
+    }
+
+    fn try_get_items(&self, key: &K) -> Result<Vec<V>, MdbmError> {
+        self.cache_bucket(key.as_ref())?;
+        let items = self.cache.borrow_mut().get_mut(key.as_ref())
+            .map(|bucket| bucket.to_owned())
+            .unwrap_or_default();
+        Ok(items)
+    }
+
+    fn try_remove_item(&mut self, key: &K, idx: usize) -> Result<(), MdbmError> {
+        self.cache_bucket(key.as_ref())?;
+        let mut bucket = match self.cache.borrow_mut().get_mut(key.as_ref()) {
+            Some(bucket) if idx < bucket.len() => bucket.clone(),
+            _ => return Ok(()),
+        };
+        bucket.remove(idx);
+        if bucket.is_empty() {
+            return self.try_remove(key);
         }
+        self.update_bucket(key.as_ref(), bucket)
+    }
+
+    fn try_insert(&mut self, key: K, value: V) -> Result<(), MdbmError> {
+        self.check_health()?;
+        self.cache_bucket(key.as_ref())?;
+        let mut bucket = self.cache.borrow_mut().get_mut(key.as_ref())
+            .map(|bucket| bucket.clone())
+            .unwrap_or_default();
+        self.policy.add(&mut bucket, value);
+        self.update_bucket(key.as_ref(), bucket)
     }
+}
 
-    fn get_item(&self, key: &K) -> Option<V> {
-        if let Some(val) = self.cache.borrow_mut().get_mut(key.as_ref()) {
-            return Some(val.to_owned())
+/// Errors are logged, and otherwise treated as missing data.
+impl<K, V, C> Store<K, V> for ChunkedStore<V, C>
+    where K: AsRef<[u8]>,
+          V: Serialize + DeserializeOwned + Clone,
+          C: Chunk,
+{
+    fn remove(&mut self, key: &K) {
+        if let Err(e) = self.try_remove(key) {
+            eprintln!("mdbm remove failed: {}", e);
         }
+    }
 
-        for chunk in &self.chunks {
-            if let Ok(val) = chunk.fetch(key.as_ref()) {
-                let val: V = val.deserialize().unwrap();
-                self.cache.borrow_mut().insert(key.as_ref().to_owned(), val.clone());
-                return Some(val)
-            }
+    fn get_items(&self, key: &K) -> Vec<V> {
+        self.try_get_items(key).unwrap_or_else(|e| {
+            eprintln!("mdbm read failed: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn remove_item(&mut self, key: &K, idx: usize) {
+        if let Err(e) = self.try_remove_item(key, idx) {
+            eprintln!("mdbm remove failed: {}", e);
         }
-        None
     }
 
     fn insert(&mut self, key: K, value: V) {
-        self.check_health();
-        self.cache.borrow_mut().insert(key.as_ref().to_owned(), value);
+        if let Err(e) = self.try_insert(key, value) {
+            eprintln!("mdbm insert failed: {}", e);
+        }
     }
 }
 
-impl<V: Serialize> Drop for Mdbm<V> {
+impl<V: Serialize, C: Chunk> Drop for ChunkedStore<V, C> {
     fn drop(&mut self) {
+        let writable = |c: &&mut ChunkFile<C>| !c.corrupt.get() && c.version == FORMAT_VERSION;
+        let chunk = match self.chunks.iter_mut().rev().find(writable) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        let mut failed = false;
         while let Some((k, v)) = self.cache.borrow_mut().remove_lru() {
-            let _ = self.chunks.last_mut().unwrap().store(k, &v);
+            if !self.dirty.remove(&k) { continue }
+            if let Err(e) = chunk.chunk.store(&k, &v) {
+                eprintln!("failed to write mdbm cache to {:?}: {}", chunk.path, e);
+                failed = true;
+            }
+        }
+        if let Err(e) = chunk.chunk.sync() {
+            eprintln!("failed to sync mdbm chunk {:?}: {}", chunk.path, e);
+            failed = true;
+        }
+        // keep the log if anything is missing from the chunk
+        if !failed {
+            if let Err(e) = self.wal.clear() {
+                eprintln!("failed to clear mdbm write-ahead log: {}", e);
+            }
         }
     }
 }
 
-fn iter_dbm_paths(dir: &Path) -> io::Result<Box<Iterator<Item=PathBuf>>> {
+fn iter_chunk_paths(dir: &Path, extension: &'static str)
+    -> io::Result<Box<Iterator<Item=PathBuf>>>
+{
     let contents = dir.read_dir()?;
     let iter = contents.flat_map(Result::ok)
         .map(|p| p.path())
-        .filter(|p| {
-            p.extension().and_then(OsStr::to_str).unwrap_or("") == "dbm"
+        .filter(move |p| {
+            p.extension().and_then(OsStr::to_str).unwrap_or("") == extension
         });
     Ok(Box::new(iter))
 }
 
+/// Copies each gdbm chunk in `from` into a log chunk in `to`, keeping its
+/// creation date, so that an `Mdbm` can be reopened as a `LogStore`.
+/// Returns the number of chunks converted.
+///
+/// Fails if the store's write-ahead log holds changes from a crash, since
+/// they aren't in any chunk yet.
+///
+/// Values are copied as they are stored, so this doesn't need to know
+/// the type of the values.
+pub fn convert_dbm_chunks<P, Q>(from: P, to: Q) -> Result<usize, MdbmError>
+    where P: AsRef<Path>,
+          Q: AsRef<Path>,
+{
+    // changes in the log would otherwise be left out
+    let wal_path = from.as_ref().join(WAL_FILE_NAME);
+    if wal_path.exists() && wal_path.metadata()?.len() > 0 {
+        return Err(MdbmError::UnrecoveredLog(wal_path));
+    }
+    let to = to.as_ref();
+    DirBuilder::new().recursive(true).create(to)?;
+    let mut converted = 0;
+    for path in iter_chunk_paths(from.as_ref(), GdbmChunk::EXTENSION)? {
+        let (chunk, created_at) = GdbmChunk::open(&path)?;
+        let version = chunk.version()?;
+        let dest = to.join(path.file_stem().expect("chunk paths have file names"))
+            .with_extension(LogChunk::EXTENSION);
+        let mut log = LogChunk::create(&dest, created_at)?;
+        for (key, value) in chunk.0.iter() {
+            if is_metadata_key(key.as_bytes()) { continue }
+            if version == 1 {
+                // encode the value as a bucket of one
+                let mut bucket = bincode::serialize(&1u64)?;
+                bucket.extend_from_slice(value.as_bytes());
+                log.store_bytes(key.as_bytes(), &bucket)?;
+            } else {
+                log.store_bytes(key.as_bytes(), value.as_bytes())?;
+            }
+        }
+        eprintln!("converted {:?} to {:?}", path, dest);
+        converted += 1;
+    }
+    Ok(converted)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::mem;
     use super::*;
     use tempdir::TempDir;
+    use anagrams::Eviction;
 
     #[test]
     fn smoke_test() {
@@ -179,13 +1103,480 @@ mod tests {
                 db.insert(key, value);
             }
             assert_eq!(db.chunks.len(), 1);
-            let item = db.get_item(&String::from("key 1"));
-            assert_eq!(item, Some("value 1".to_string()));
+            let item = db.get_items(&String::from("key 1"));
+            assert_eq!(item, vec!["value 1".to_string()]);
         }
         // reopen and check that our data was saved
-        let db = Mdbm::new(tempdir.path(), 10);
+        let db: Mdbm<String> = Mdbm::new(tempdir.path(), 10);
         assert_eq!(db.chunks.len(), 1);
-        let item = db.get_item(&String::from("key 2"));
-        assert_eq!(item, Some("value 2".to_string()));
+        let item = db.get_items(&String::from("key 2"));
+        assert_eq!(item, vec!["value 2".to_string()]);
+    }
+
+    #[test]
+    fn buckets() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let key = String::from("key");
+        {
+            let policy = BucketPolicy::new(3, Eviction::Oldest);
+            let mut db = Mdbm::with_policy(tempdir.path(), 10, policy);
+            for i in 0..5 {
+                db.insert(key.clone(), i);
+            }
+            assert_eq!(db.get_items(&key), vec![2, 3, 4]);
+            db.remove_item(&key, 1);
+            assert_eq!(db.get_items(&key), vec![2, 4]);
+        }
+        let mut db: Mdbm<i32> = Mdbm::new(tempdir.path(), 10);
+        assert_eq!(db.get_items(&key), vec![2, 4]);
+        db.remove_item(&key, 0);
+        db.remove_item(&key, 0);
+        assert!(db.get_items(&key).is_empty());
+    }
+
+    #[test]
+    fn locked_chunks_are_not_quarantined() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let mut db = Mdbm::open(tempdir.path(), 10).unwrap();
+        db.insert(String::from("key"), 1);
+        let path = db.chunks[0].path.clone();
+
+        // gdbm won't open a chunk for writing twice
+        match Mdbm::<i32>::open(tempdir.path(), 10) {
+            Err(MdbmError::Gdbm(_)) => (),
+            Err(e) => panic!("expected a gdbm error, found {:?}", e),
+            Ok(_) => panic!("expected a gdbm error"),
+        }
+        assert!(db.quarantined().is_empty());
+        assert!(path.exists());
+        assert_eq!(db.get_items(&String::from("key")), vec![1]);
+    }
+
+    /// A log chunk which fails to read `FLAKY_KEY` or to write
+    /// `UNWRITABLE_KEY`, as if the disk had failed.
+    struct FlakyChunk(LogChunk);
+
+    const FLAKY_KEY: &str = "flaky";
+    const UNWRITABLE_KEY: &str = "unwritable";
+
+    impl ReadChunk for FlakyChunk {
+        fn fetch<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>, MdbmError> {
+            if key == FLAKY_KEY.as_bytes() {
+                return Err(io::Error::from_raw_os_error(::libc::EIO).into());
+            }
+            self.0.fetch(key)
+        }
+
+        fn count(&self) -> Result<usize, MdbmError> { self.0.count() }
+        fn keys(&self) -> Result<Vec<Vec<u8>>, MdbmError> { self.0.keys() }
+        fn contains_key(&self, key: &[u8]) -> Result<bool, MdbmError> { self.0.contains_key(key) }
+        fn version(&self) -> Result<u32, MdbmError> { self.0.version() }
+    }
+
+    impl Chunk for FlakyChunk {
+        const EXTENSION: &'static str = LogChunk::EXTENSION;
+        type Reader = LogChunk;
+
+        fn open(path: &Path) -> Result<(Self, DateTime<Utc>), MdbmError> {
+            LogChunk::open(path).map(|(chunk, created_at)| (FlakyChunk(chunk), created_at))
+        }
+
+        fn open_reader(path: &Path) -> Result<(LogChunk, DateTime<Utc>), MdbmError> {
+            LogChunk::open_reader(path)
+        }
+
+        fn create(path: &Path, created_at: DateTime<Utc>) -> Result<Self, MdbmError> {
+            LogChunk::create(path, created_at).map(FlakyChunk)
+        }
+
+        fn store<V: Serialize>(&mut self, key: &[u8], value: &V) -> Result<(), MdbmError> {
+            if key == UNWRITABLE_KEY.as_bytes() {
+                return Err(io::Error::from_raw_os_error(::libc::EIO).into());
+            }
+            self.0.store(key, value)
+        }
+
+        fn remove(&mut self, key: &[u8]) -> Result<(), MdbmError> { self.0.remove(key) }
+        fn sync(&mut self) -> Result<(), MdbmError> { self.0.sync() }
+    }
+
+    #[test]
+    fn failed_reads_are_not_quarantined() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let key = String::from("key");
+        let mut db: ChunkedStore<i32, FlakyChunk> = ChunkedStore::new(tempdir.path(), 10);
+        db.insert(key.clone(), 1);
+        let path = db.chunks[0].path.clone();
+        match db.try_get_items(&String::from(FLAKY_KEY)) {
+            Err(MdbmError::Io(_)) => (),
+            other => panic!("expected an io error, found {:?}", other),
+        }
+        // the chunk is still used
+        db.insert(key.clone(), 2);
+        assert!(db.quarantined().is_empty());
+        assert_eq!(db.chunks.len(), 1);
+        assert!(path.exists());
+        assert_eq!(db.get_items(&key), vec![1, 2]);
+    }
+
+    #[test]
+    fn failed_checkpoint_keeps_log() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let wal_path = tempdir.path().join(WAL_FILE_NAME);
+        let mut db: ChunkedStore<i32, FlakyChunk> = ChunkedStore::new(tempdir.path(), 10);
+        db.insert(String::from("key"), 1);
+        db.insert(String::from(UNWRITABLE_KEY), 2);
+        assert!(db.checkpoint().is_err());
+        assert_eq!(db.dirty.len(), 2);
+        assert!(wal_path.metadata().unwrap().len() > 0);
+        // the bucket that couldn't be written is still in the log
+        drop(db);
+        assert!(wal_path.metadata().unwrap().len() > 0);
+    }
+
+    #[test]
+    fn quarantine_corrupt_chunks() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let garbage = tempdir.path().join("garbage.dbm");
+        fs::write(&garbage, b"not a gdbm file").unwrap();
+        let key = String::from("key");
+        {
+            let mut db = Mdbm::open(tempdir.path(), 10).unwrap();
+            assert_eq!(db.quarantined(), &[tempdir.path().join("garbage.dbm.corrupt")]);
+            assert!(!garbage.exists());
+            db.insert(key.clone(), 1);
+        }
+
+        // write a bucket of the wrong type directly into the chunk
+        let mut db: Mdbm<i32> = Mdbm::open(tempdir.path(), 10).unwrap();
+        assert!(db.quarantined().is_empty());
+        db.chunks[0].chunk.store(key.as_bytes(), &"not a bucket").unwrap();
+        match db.try_get_items(&key) {
+            Err(MdbmError::Corrupt { ref path, .. }) => assert_eq!(path, &db.chunks[0].path),
+            other => panic!("expected corrupt chunk, found {:?}", other),
+        }
+        // the corrupt chunk is skipped, and replaced on the next write
+        assert!(db.get_items(&key).is_empty());
+        db.insert(key.clone(), 2);
+        assert_eq!(db.quarantined().len(), 1);
+        assert_eq!(db.chunks.len(), 1);
+        assert_eq!(db.get_items(&key), vec![2]);
+    }
+
+    fn chunk_names(dir: &Path) -> Vec<String> {
+        let mut names = iter_chunk_paths(dir, "dbm").unwrap()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn read_open_store() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let wal_path = tempdir.path().join(WAL_FILE_NAME);
+        {
+            let mut db = Mdbm::open(tempdir.path(), 10).unwrap();
+            db.insert(String::from("old"), 1);
+        }
+        let mut db: Mdbm<i32> = Mdbm::open(tempdir.path(), 10).unwrap();
+        db.insert(String::from("new"), 2);
+        let wal_len = wal_path.metadata().unwrap().len();
+        assert!(wal_len > 0);
+
+        // the store stays locked and untouched while it's being read
+        {
+            let reader: StoreReader<i32, GdbmChunk> = StoreReader::open(tempdir.path()).unwrap();
+            let stats = reader.chunk_stats().unwrap();
+            assert_eq!(stats.len(), 1);
+            assert_eq!(stats[0].entries, 1);
+            let entries = reader.entries().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(entries, vec![(b"old".to_vec(), vec![1])]);
+        }
+        assert_eq!(wal_path.metadata().unwrap().len(), wal_len);
+        assert!(Mdbm::<i32>::open(tempdir.path(), 10).is_err());
+        assert_eq!(db.get_items(&String::from("new")), vec![2]);
+    }
+
+    #[test]
+    fn retention() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let archive = tempdir.path().join("archive");
+        let retention = RetentionPolicy {
+            max_chunks: Some(3),
+            archive_dir: Some(archive.clone()),
+            ..Default::default()
+        };
+        let mut db: Mdbm<i32> = Mdbm::open(tempdir.path(), 10).unwrap()
+            .with_retention(retention);
+        let mut created = Vec::new();
+        for _ in 0..10 {
+            db.add_chunk().unwrap();
+            created.push(db.chunks.last().unwrap().path.clone());
+        }
+        let kept = db.chunks.iter().map(|c| c.path.clone()).collect::<Vec<_>>();
+        assert_eq!(kept, &created[7..]);
+        assert_eq!(chunk_names(tempdir.path()).len(), 3);
+        assert_eq!(chunk_names(&archive).len(), 7);
+        assert!(archive.join(created[0].file_name().unwrap()).exists());
+
+        // size limit: keep as many of the newest chunks as fit
+        let chunk_len = created[9].metadata().unwrap().len();
+        db.retention = RetentionPolicy { max_bytes: Some(chunk_len * 2), ..Default::default() };
+        assert_eq!(db.enforce_retention().unwrap(), &created[7..8]);
+        assert!(!created[7].exists());
+        assert_eq!(db.chunks.len(), 2);
+    }
+
+    #[test]
+    fn retention_drops_cached_buckets() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let old = String::from("old");
+        let kept = String::from("kept");
+        {
+            let mut db = Mdbm::open(tempdir.path(), 10).unwrap();
+            db.insert(old.clone(), 1);
+            db.insert(kept.clone(), 2);
+        }
+        let retention = RetentionPolicy { max_chunks: Some(2), ..Default::default() };
+        let mut db: Mdbm<i32> = Mdbm::open(tempdir.path(), 10).unwrap()
+            .with_retention(retention);
+        // read both buckets into the cache; `kept` is also in the new chunk
+        assert_eq!(db.get_items(&old), vec![1]);
+        assert_eq!(db.get_items(&kept), vec![2]);
+        db.add_chunk().unwrap();
+        db.chunks[1].chunk.store(kept.as_bytes(), &vec![2]).unwrap();
+        db.add_chunk().unwrap();
+        assert_eq!(db.chunks.len(), 2);
+        assert!(db.get_items(&old).is_empty());
+        assert_eq!(db.get_items(&kept), vec![2]);
+
+        drop(db);
+        let db: Mdbm<i32> = Mdbm::open(tempdir.path(), 10).unwrap();
+        assert!(db.get_items(&old).is_empty());
+        assert_eq!(db.get_items(&kept), vec![2]);
+    }
+
+    #[test]
+    fn retention_by_age() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let now = Utc::now();
+        for days in &[3, 2, 1] {
+            let created_at = now - ::chrono::Duration::days(*days);
+            let path = tempdir.path().join(format!("{}_days.dbm", days));
+            GdbmChunk::create(&path, created_at).unwrap();
+        }
+        let retention = RetentionPolicy {
+            max_age: Some(Duration::from_secs(60 * 60 * 12)),
+            ..Default::default()
+        };
+        let mut db: Mdbm<i32> = Mdbm::open(tempdir.path(), 10).unwrap()
+            .with_retention(retention);
+        assert_eq!(db.chunks.len(), 3);
+        db.enforce_retention().unwrap();
+        // the newest chunk is kept, even though it's too old
+        assert_eq!(chunk_names(tempdir.path()), vec!["1_days.dbm"]);
+        db.add_chunk().unwrap();
+        assert_eq!(db.chunks.len(), 1);
+        assert!(!tempdir.path().join("1_days.dbm").exists());
+    }
+
+    #[test]
+    fn recover_after_crash() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let wal_path = tempdir.path().join(WAL_FILE_NAME);
+        let chunk_path;
+        {
+            let mut db = Mdbm::new(tempdir.path(), 10);
+            db.insert(String::from("zero"), 0);
+            db.checkpoint().unwrap();
+            assert_eq!(wal_path.metadata().unwrap().len(), 0);
+            db.insert(String::from("one"), 1);
+            db.insert(String::from("one"), 11);
+            db.insert(String::from("two"), 2);
+            db.insert(String::from("three"), 3);
+            db.remove(&String::from("two"));
+            db.remove_item(&String::from("one"), 0);
+            // nothing has been written to the chunk since the checkpoint
+            assert_eq!(db.chunks[0].chunk.count().unwrap(), 1);
+            assert!(wal_path.metadata().unwrap().len() > 0);
+            chunk_path = db.chunks[0].path.clone();
+            // close the chunks, releasing their locks, without writing the cache
+            db.chunks.clear();
+            mem::forget(db);
+        }
+
+        let mut db: Mdbm<i32> = Mdbm::new(tempdir.path(), 10);
+        assert!(db.quarantined().is_empty());
+        assert_eq!(wal_path.metadata().unwrap().len(), 0);
+        assert_eq!(db.chunks.len(), 1);
+        assert_eq!(db.chunks[0].path, chunk_path);
+        assert_eq!(db.chunks[0].chunk.count().unwrap(), 3);
+        assert_eq!(db.get_items(&String::from("zero")), vec![0]);
+        assert_eq!(db.get_items(&String::from("one")), vec![11]);
+        assert!(db.get_items(&String::from("two")).is_empty());
+        assert_eq!(db.get_items(&String::from("three")), vec![3]);
+
+        // a clean shutdown leaves nothing to recover
+        db.insert(String::from("four"), 4);
+        drop(db);
+        assert_eq!(wal_path.metadata().unwrap().len(), 0);
+        let db: Mdbm<i32> = Mdbm::new(tempdir.path(), 10);
+        assert_eq!(db.get_items(&String::from("four")), vec![4]);
+    }
+
+    #[test]
+    fn checkpoint_writes_changed_buckets() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        {
+            let mut db = LogStore::new(tempdir.path(), 10);
+            db.insert(String::from("one"), 1);
+            db.insert(String::from("two"), 2);
+        }
+        let mut db: LogStore<i32> = LogStore::new(tempdir.path(), 10);
+        let chunk_path = db.chunks[0].path.clone();
+        let chunk_len = chunk_path.metadata().unwrap().len();
+        // reading a bucket caches it, but doesn't change it
+        assert_eq!(db.get_items(&String::from("one")), vec![1]);
+        db.checkpoint().unwrap();
+        assert_eq!(chunk_path.metadata().unwrap().len(), chunk_len);
+
+        db.insert(String::from("two"), 22);
+        db.checkpoint().unwrap();
+        let new_len = chunk_path.metadata().unwrap().len();
+        assert!(new_len > chunk_len);
+        drop(db);
+        // nothing changed since the checkpoint, so nothing is written on closing
+        assert_eq!(chunk_path.metadata().unwrap().len(), new_len);
+        let db: LogStore<i32> = LogStore::new(tempdir.path(), 10);
+        assert_eq!(db.get_items(&String::from("two")), vec![2, 22]);
+    }
+
+    #[test]
+    fn log_store() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let key = String::from("key");
+        {
+            let policy = BucketPolicy::new(3, Eviction::Oldest);
+            let mut db = LogStore::with_policy(tempdir.path(), 10, policy);
+            for i in 0..5 {
+                db.insert(key.clone(), i);
+            }
+            db.insert(String::from("other"), 10);
+            db.remove_item(&key, 1);
+            assert_eq!(db.get_items(&key), vec![2, 4]);
+        }
+        assert_eq!(iter_chunk_paths(tempdir.path(), "log").unwrap().count(), 1);
+        let mut db: LogStore<i32> = LogStore::new(tempdir.path(), 10);
+        assert_eq!(db.get_items(&key), vec![2, 4]);
+        db.remove(&key);
+        assert!(db.get_items(&key).is_empty());
+        assert_eq!(db.get_items(&String::from("other")), vec![10]);
+    }
+
+    #[test]
+    fn legacy_chunks() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let key = |k: &str| k.to_string();
+        // a chunk from before buckets, holding a single value per key
+        {
+            let path = tempdir.path().join("legacy.dbm");
+            let mut db = GdbmOpener::new().create(true).readwrite(&path).unwrap();
+            db.store(DB_CREATION_DATE_KEY.as_bytes(), &Utc::now()).unwrap();
+            db.store("one".as_bytes(), &key("old one")).unwrap();
+            db.store("two".as_bytes(), &key("old two")).unwrap();
+        }
+        {
+            let mut db: Mdbm<String> = Mdbm::new(tempdir.path(), 10);
+            assert_eq!(db.chunks[0].version, 1);
+            assert_eq!(db.chunks[0].chunk.count().unwrap(), 2);
+            assert_eq!(db.get_items(&key("one")), vec![key("old one")]);
+            // the old chunk is never written to
+            db.insert(key("one"), key("new one"));
+            assert_eq!(db.chunks.len(), 2);
+            assert_eq!(db.chunks[1].version, FORMAT_VERSION);
+        }
+        {
+            let db: Mdbm<String> = Mdbm::new(tempdir.path(), 10);
+            assert!(db.quarantined().is_empty());
+            assert_eq!(db.get_items(&key("one")), vec![key("old one"), key("new one")]);
+            assert_eq!(db.get_items(&key("two")), vec![key("old two")]);
+        }
+
+        let log_dir = tempdir.path().join("log");
+        assert_eq!(convert_dbm_chunks(tempdir.path(), &log_dir).unwrap(), 2);
+        let db: LogStore<String> = LogStore::new(&log_dir, 10);
+        assert_eq!(db.get_items(&key("two")), vec![key("old two")]);
+        assert_eq!(db.get_items(&key("one")), vec![key("old one"), key("new one")]);
+    }
+
+    #[test]
+    fn convert() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let dbm_dir = tempdir.path().join("dbm");
+        let log_dir = tempdir.path().join("log");
+        {
+            let mut db = Mdbm::new(&dbm_dir, 10);
+            for i in 0..5 {
+                db.insert(format!("key {}", i), format!("value {}", i));
+            }
+            db.add_chunk().unwrap();
+            db.insert(String::from("key 1"), String::from("newer value"));
+            // crash, leaving the newer value only in the write-ahead log
+            db.chunks.clear();
+            mem::forget(db);
+        }
+        match convert_dbm_chunks(&dbm_dir, &log_dir) {
+            Err(MdbmError::UnrecoveredLog(ref path)) => assert_eq!(path, &dbm_dir.join(WAL_FILE_NAME)),
+            other => panic!("expected an unrecovered log, found {:?}", other),
+        }
+        assert!(!log_dir.exists());
+        drop(Mdbm::<String>::new(&dbm_dir, 10));
+        assert_eq!(convert_dbm_chunks(&dbm_dir, &log_dir).unwrap(), 2);
+
+        let db: LogStore<String> = LogStore::new(&log_dir, 10);
+        assert_eq!(db.chunks.len(), 2);
+        assert_eq!(db.get_items(&String::from("key 2")), vec!["value 2".to_string()]);
+        assert_eq!(db.get_items(&String::from("key 1")),
+                   vec!["value 1".to_string(), "newer value".to_string()]);
+    }
+
+    #[test]
+    fn entries() {
+        let tempdir = TempDir::new("mdbm_test").unwrap();
+        let mut db = Mdbm::new(tempdir.path(), 10);
+        db.insert(String::from("one"), 1);
+        db.insert(String::from("two"), 2);
+        // move the cache to disk
+        while let Some((k, v)) = db.cache.borrow_mut().remove_lru() {
+            db.chunks[0].chunk.store(&k, &v).unwrap();
+        }
+        db.add_chunk().unwrap();
+        db.chunks[1].chunk.store(b"two", &vec![2, 22]).unwrap();
+        db.insert(String::from("three"), 3);
+
+        let stats = db.chunk_stats().unwrap();
+        assert_eq!(stats.iter().map(|s| s.entries).collect::<Vec<_>>(), vec![2, 1]);
+
+        let mut entries = db.entries().collect::<Result<Vec<_>, _>>().unwrap();
+        entries.sort();
+        assert_eq!(entries, vec![(b"one".to_vec(), vec![1]),
+                                 (b"three".to_vec(), vec![3]),
+                                 (b"two".to_vec(), vec![2, 22])]);
+
+        let mut exported = Vec::new();
+        assert_eq!(db.export_jsonl(&mut exported).unwrap(), 3);
+        let other_dir = TempDir::new("mdbm_test").unwrap();
+        let mut other: LogStore<i32> = LogStore::new(other_dir.path(), 10);
+        assert_eq!(other.import_jsonl(exported.as_slice()).unwrap(), 3);
+        assert_eq!(other.get_items(&String::from("two")), vec![2, 22]);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(to_hex(&[0, 1, 0xab, 0xff]), "0001abff");
+        assert_eq!(from_hex("0001abff"), Some(vec![0, 1, 0xab, 0xff]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
     }
 }
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::str;
+use std::collections::BTreeMap;
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use anagrams::Fingerprint;
+use filters::is_ascii_letter;
+use fold::fold_latin;
+
+const HIRAGANA_KATAKANA_OFFSET: u32 = 0x60;
+
+/// The writing systems we know how to fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Hebrew,
+    /// Hiragana and katakana, which share a fingerprint alphabet.
+    Kana,
+}
+
+/// A fingerprint over an arbitrary alphabet.
+///
+/// This is the sorted letter counts of an item, serialized as a tag byte
+/// for the script followed by each letter (as utf-8) and its count.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct ScriptFingerprint(Vec<u8>);
+
+impl Script {
+    /// Returns the script of a single letter, if it is a letter in
+    /// some script we support.
+    pub fn of(chr: char) -> Option<Script> {
+        if is_ascii_letter(&chr) { return Some(Script::Latin) }
+        if !chr.is_alphabetic() { return None }
+        match chr as u32 {
+            0x00C0 ..= 0x024F | 0x1E00 ..= 0x1EFF | 0xFB00 ..= 0xFB06 => Some(Script::Latin),
+            0x0370 ..= 0x03FF | 0x1F00 ..= 0x1FFF => Some(Script::Greek),
+            0x0400 ..= 0x052F => Some(Script::Cyrillic),
+            0x0590 ..= 0x05FF | 0xFB1D ..= 0xFB4F => Some(Script::Hebrew),
+            0x3041 ..= 0x3096 | 0x30A1 ..= 0x30FA | 0x30FC | 0xFF66 ..= 0xFF9D => {
+                Some(Script::Kana)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the script used by the majority of letters in `s`.
+    pub fn detect(s: &str) -> Option<Script> {
+        let mut counts = BTreeMap::new();
+        for script in s.chars().flat_map(Script::of) {
+            *counts.entry(script).or_insert(0usize) += 1;
+        }
+        counts.into_iter()
+            .max_by_key(|&(_, count)| count)
+            .map(|(script, _)| script)
+    }
+
+    /// Normalizes the letters in `s` for this script.
+    ///
+    /// Letters are lowercased, and variants that shouldn't count as
+    /// distinct letters (accents, final forms, katakana vs hiragana)
+    /// are folded together. Other characters are left in place.
+    pub fn normalize(self, s: &str) -> String {
+        match self {
+            Script::Latin => fold_latin(s).to_lowercase(),
+            Script::Greek => s.nfkd()
+                .filter(|c| !is_combining_mark(*c))
+                .flat_map(char::to_lowercase)
+                .map(|c| if c == 'ς' { 'σ' } else { c })
+                .collect(),
+            // NFKD would split 'й' into 'и' + breve, which are different letters.
+            Script::Cyrillic => s.nfc()
+                .flat_map(char::to_lowercase)
+                .map(|c| if c == 'ё' { 'е' } else { c })
+                .collect(),
+            Script::Hebrew => s.nfkd()
+                .filter(|c| !is_combining_mark(*c))
+                .map(hebrew_non_final)
+                .collect(),
+            // NFKC folds half-width katakana and recomposes dakuten.
+            Script::Kana => s.nfkc().map(katakana_to_hiragana).collect(),
+        }
+    }
+
+    /// Returns `true` if `chr` is a letter in this script.
+    pub fn is_letter(self, chr: &char) -> bool {
+        Script::of(*chr) == Some(self)
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Script::Latin => 1,
+            Script::Greek => 2,
+            Script::Cyrillic => 3,
+            Script::Hebrew => 4,
+            Script::Kana => 5,
+        }
+    }
+}
+
+fn hebrew_non_final(chr: char) -> char {
+    match chr {
+        'ך' => 'כ',
+        'ם' => 'מ',
+        'ן' => 'נ',
+        'ף' => 'פ',
+        'ץ' => 'צ',
+        other => other,
+    }
+}
+
+fn katakana_to_hiragana(chr: char) -> char {
+    match chr as u32 {
+        0x30A1 ..= 0x30F6 => {
+            ::std::char::from_u32(chr as u32 - HIRAGANA_KATAKANA_OFFSET).unwrap_or(chr)
+        }
+        _ => chr,
+    }
+}
+
+impl ScriptFingerprint {
+    /// Fingerprints `s` using the alphabet of its detected script.
+    pub fn new(s: &str) -> Self {
+        let script = match Script::detect(s) {
+            Some(script) => script,
+            None => return ScriptFingerprint(Vec::new()),
+        };
+        let mut counts: BTreeMap<char, u8> = BTreeMap::new();
+        for chr in script.normalize(s).chars().filter(|c| script.is_letter(c)) {
+            let count = counts.entry(chr).or_insert(0);
+            *count = count.saturating_add(1);
+        }
+
+        let mut bytes = vec![script.tag()];
+        let mut buf = [0u8; 4];
+        for (chr, count) in counts {
+            bytes.extend_from_slice(chr.encode_utf8(&mut buf).as_bytes());
+            bytes.push(count);
+        }
+        ScriptFingerprint(bytes)
+    }
+
+    /// Iterates over each letter in the fingerprint and its count.
+    pub fn letters<'a>(&'a self) -> Letters<'a> {
+        // skip the script tag
+        let start = self.0.len().min(1);
+        Letters(&self.0[start..])
+    }
+}
+
+/// An iterator over the letters and counts in a `ScriptFingerprint`.
+pub struct Letters<'a>(&'a [u8]);
+
+impl<'a> Iterator for Letters<'a> {
+    type Item = (char, u8);
+
+    fn next(&mut self) -> Option<(char, u8)> {
+        let lead = *self.0.first()?;
+        let len = match lead {
+            0x00 ..= 0x7F => 1,
+            0xC0 ..= 0xDF => 2,
+            0xE0 ..= 0xEF => 3,
+            _ => 4,
+        };
+        let chr = str::from_utf8(&self.0[..len]).ok()?.chars().next()?;
+        let count = self.0[len];
+        self.0 = &self.0[len + 1..];
+        Some((chr, count))
+    }
+}
+
+impl fmt::Display for ScriptFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut result = String::new();
+        for (chr, count) in self.letters() {
+            for _ in 0..count {
+                result.push(chr);
+            }
+        }
+        write!(f, "{}", result)
+    }
+}
+
+impl AsRef<[u8]> for ScriptFingerprint {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Fingerprint for ScriptFingerprint {
+    fn len(&self) -> usize {
+        self.letters().map(|(_, count)| count as usize).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detection() {
+        assert_eq!(Script::detect("hello there"), Some(Script::Latin));
+        assert_eq!(Script::detect("Привет, мир"), Some(Script::Cyrillic));
+        assert_eq!(Script::detect("Καλημέρα κόσμε"), Some(Script::Greek));
+        assert_eq!(Script::detect("שלום עולם"), Some(Script::Hebrew));
+        assert_eq!(Script::detect("こんにちは カタカナ"), Some(Script::Kana));
+        assert_eq!(Script::detect("123 😒"), None);
+        // mostly cyrillic, with a latin hashtag
+        assert_eq!(Script::detect("Доброе утро #tbt"), Some(Script::Cyrillic));
+    }
+
+    #[test]
+    fn fingerprints() {
+        let one = ScriptFingerprint::new("Апельсин");
+        let two = ScriptFingerprint::new("спаниель");
+        assert_eq!(one, two);
+        assert_eq!(one.to_string(), "аеилнпсь");
+
+        // accents and final sigma are folded
+        assert_eq!(ScriptFingerprint::new("σάλος"), ScriptFingerprint::new("ΣΑΛΟΣ"));
+        // final forms are folded
+        assert_eq!(ScriptFingerprint::new("מים"), ScriptFingerprint::new("ממי"));
+        // katakana and hiragana share an alphabet
+        assert_eq!(ScriptFingerprint::new("カタ"), ScriptFingerprint::new("たか"));
+        assert_ne!(ScriptFingerprint::new("が"), ScriptFingerprint::new("か"));
+
+        // the same letters in different scripts never collide
+        assert_ne!(ScriptFingerprint::new("ao"), ScriptFingerprint::new("ао"));
+    }
+
+    #[test]
+    fn latin_matches_fold() {
+        let one = ScriptFingerprint::new("Straße");
+        assert_eq!(one.to_string(), "aerssst");
+        assert_eq!(one.letters().map(|(_, c)| c as usize).sum::<usize>(), 7);
+        assert_eq!(Fingerprint::len(&one), 7);
+        assert_eq!(Fingerprint::len(&ScriptFingerprint::new("Апельсин")), 8);
+        assert!(Fingerprint::is_empty(&ScriptFingerprint::new("123")));
+    }
+}
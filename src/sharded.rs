@@ -0,0 +1,161 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+use anagrams::{AsStr, Adapter, Tester, Store, BucketPolicy, MemoryStore, process_item_impl};
+
+/// An in memory store which can be shared between threads.
+///
+/// Keys are split across a number of independently locked `MemoryStore`s
+/// by hash, so workers handling items with different fingerprints rarely
+/// contend for the same lock.
+pub struct ShardedStore<K, V> {
+    shards: Vec<Mutex<MemoryStore<K, V>>>,
+}
+
+impl<K: Hash + Eq, V> ShardedStore<K, V> {
+    pub fn new(n_shards: usize) -> Self {
+        ShardedStore::with_policy(n_shards, BucketPolicy::default())
+    }
+
+    pub fn with_policy(n_shards: usize, policy: BucketPolicy) -> Self {
+        assert!(n_shards > 0, "shard count must be non-zero");
+        let shards = (0..n_shards)
+            .map(|_| Mutex::new(MemoryStore::with_policy(policy)))
+            .collect();
+        ShardedStore { shards }
+    }
+
+    /// Locks and returns the shard responsible for `key`.
+    pub fn shard<'a>(&'a self, key: &K) -> MutexGuard<'a, MemoryStore<K, V>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let idx = (hasher.finish() % self.shards.len() as u64) as usize;
+        self.shards[idx].lock().unwrap()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> ShardedStore<K, V> {
+    /// Returns the candidates for `key`, oldest first.
+    pub fn get_items(&self, key: &K) -> Vec<V> {
+        self.shard(key).get_items(key)
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.shard(&key).insert(key, value)
+    }
+}
+
+/// Handles a single item, like `process_item`, with a store that may be
+/// shared with other threads.
+///
+/// Each thread should have its own adapter and tester. The shard for the
+/// item's fingerprint is locked until the item has been handled, so the
+/// adapter should not block for long.
+pub fn process_item_shared<T, A, TE>(item: T,
+                                     store: &ShardedStore<TE::Fingerprint, T>,
+                                     adapter: &mut A,
+                                     tester: &mut TE)
+    where T: AsStr + Clone,
+          A: Adapter<T, TE>,
+          TE: Tester<T>,
+{
+    let ident = tester.fingerprint(&item);
+    let mut shard = store.shard(&ident);
+    process_item_impl(item, ident, &mut *shard, adapter, tester, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use super::*;
+    use anagrams::{AsciiTester, AsciiTesterConfig, MatchScore, NormalizeBy};
+
+    /// Collects each hit as a pair sorted by text, ignoring arrival order.
+    #[derive(Default)]
+    struct PairAdapter(Vec<(String, String)>);
+
+    impl<TE: Tester<String>> Adapter<String, TE> for PairAdapter {
+        fn handle_match(&mut self, p1: &String, p2: &String,
+                        _hash: &TE::Fingerprint, _score: &MatchScore) {
+            let pair = if p1 < p2 { (p1.clone(), p2.clone()) } else { (p2.clone(), p1.clone()) };
+            self.0.push(pair);
+        }
+    }
+
+    /// Pairs of anagrams with distinct fingerprints, and some noise.
+    fn corpus() -> Vec<String> {
+        let words = ["dormitory", "dirty room", "the eyes", "they see",
+                     "a gentleman", "elegant man", "astronomer", "moon starer",
+                     "the morse code", "here come dots", "slot machines", "cash lost in me"];
+        let mut items = Vec::new();
+        for i in 0..50 {
+            // a distinct pair of letters for each copy of the corpus
+            let tag = format!("{}{}", b"bcdfghjklm"[i % 10] as char, b"npqrs"[i / 10] as char);
+            for word in words.iter() {
+                items.push(format!("{} {}", word, tag));
+            }
+            items.push(format!("{} {}", tag, i));
+        }
+        items
+    }
+
+    /// A tester whose results don't depend on which item of a pair arrived
+    /// first, since threads may handle them in either order.
+    fn symmetric_tester() -> AsciiTester {
+        let config = AsciiTesterConfig {
+            normalize_by: NormalizeBy::Shorter,
+            ..Default::default()
+        };
+        AsciiTester::new(config)
+    }
+
+    #[test]
+    fn sequential_matches_parallel() {
+        let items = corpus();
+
+        let store = ShardedStore::new(1);
+        let mut adapter = PairAdapter::default();
+        let mut tester = symmetric_tester();
+        for item in items.iter() {
+            process_item_shared(item.clone(), &store, &mut adapter, &mut tester);
+        }
+        let mut sequential = adapter.0;
+        sequential.sort();
+        assert!(sequential.len() > 100);
+
+        let store = Arc::new(ShardedStore::new(8));
+        let items = Arc::new(items);
+        let n_workers = 4;
+        let workers = (0..n_workers).map(|worker| {
+            let store = store.clone();
+            let items = items.clone();
+            thread::spawn(move || {
+                let mut adapter = PairAdapter::default();
+                let mut tester = symmetric_tester();
+                for item in items.iter().skip(worker).step_by(n_workers) {
+                    process_item_shared(item.clone(), &store, &mut adapter, &mut tester);
+                }
+                adapter.0
+            })
+        }).collect::<Vec<_>>();
+
+        let mut parallel = workers.into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect::<Vec<_>>();
+        parallel.sort();
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn shards() {
+        let store = ShardedStore::new(4);
+        for i in 0..100 {
+            store.insert(i, i.to_string());
+        }
+        assert_eq!(store.get_items(&42), vec!["42".to_string()]);
+        let total: usize = (0..100).map(|i| store.get_items(&i).len()).sum();
+        assert_eq!(total, 100);
+    }
+}
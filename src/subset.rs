@@ -0,0 +1,260 @@
+use anagrams::{AsStr, Adapter, Tester, Store, BucketPolicy, AsciiFingerprint, process_item};
+
+const ALPHABET_LEN: usize = 26;
+
+/// A store of candidates that can also find every candidate whose letters
+/// are a strict sub-multiset of some fingerprint.
+///
+/// Fingerprints are stored in a trie with one level per letter, keyed on
+/// that letter's count, so a query only descends into branches with no
+/// more of each letter than the query itself.
+pub struct SubsetIndex<V> {
+    root: Node<V>,
+    policy: BucketPolicy,
+    len: usize,
+}
+
+struct Node<V> {
+    /// Children sorted by count.
+    children: Vec<(u8, Node<V>)>,
+    /// The candidates for a complete fingerprint; only used in leaves.
+    values: Vec<V>,
+}
+
+impl<V> Node<V> {
+    fn new() -> Self {
+        Node { children: Vec::new(), values: Vec::new() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.values.is_empty()
+    }
+
+    fn child(&self, count: u8) -> Option<&Node<V>> {
+        self.children.binary_search_by_key(&count, |c| c.0).ok()
+            .map(|idx| &self.children[idx].1)
+    }
+
+    fn child_mut(&mut self, count: u8) -> &mut Node<V> {
+        let idx = match self.children.binary_search_by_key(&count, |c| c.0) {
+            Ok(idx) => idx,
+            Err(idx) => {
+                self.children.insert(idx, (count, Node::new()));
+                idx
+            }
+        };
+        &mut self.children[idx].1
+    }
+
+    /// Applies `f` to the leaf for `counts`, then prunes empty nodes.
+    /// Returns the result of `f`, if the leaf exists.
+    fn modify_leaf<F, R>(&mut self, counts: &[u8], f: F) -> Option<R>
+        where F: FnOnce(&mut Vec<V>) -> R,
+    {
+        let (count, rest) = match counts.split_first() {
+            Some(split) => split,
+            None => return Some(f(&mut self.values)),
+        };
+        let idx = self.children.binary_search_by_key(count, |c| c.0).ok()?;
+        let result = self.children[idx].1.modify_leaf(rest, f);
+        if self.children[idx].1.is_empty() {
+            self.children.remove(idx);
+        }
+        result
+    }
+
+    fn collect_subsets<'a>(&'a self, query: &[u8], remaining: usize,
+                           min_letters: usize, letters: usize, strict: bool,
+                           out: &mut Vec<&'a V>) {
+        let (max, rest) = match query.split_first() {
+            Some(split) => split,
+            None => {
+                if strict && letters >= min_letters {
+                    out.extend(self.values.iter());
+                }
+                return
+            }
+        };
+        let remaining = remaining - *max as usize;
+        for &(count, ref child) in self.children.iter() {
+            if count > *max { break }
+            let letters = letters + count as usize;
+            // even taking every remaining letter won't be enough
+            if letters + remaining < min_letters { continue }
+            child.collect_subsets(rest, remaining, min_letters, letters,
+                                  strict || count < *max, out);
+        }
+    }
+}
+
+impl<V> SubsetIndex<V> {
+    pub fn new() -> Self {
+        SubsetIndex::with_policy(BucketPolicy::default())
+    }
+
+    pub fn with_policy(policy: BucketPolicy) -> Self {
+        SubsetIndex { root: Node::new(), policy, len: 0 }
+    }
+
+    /// The number of candidates in the index.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns every candidate whose letters are a strict sub-multiset of
+    /// `key`, and which has at least `min_letters` letters.
+    pub fn subsets(&self, key: &AsciiFingerprint, min_letters: usize) -> Vec<&V> {
+        let query = key.as_ref();
+        let mut out = Vec::new();
+        self.root.collect_subsets(query, key.len(), min_letters, 0, false, &mut out);
+        out
+    }
+
+    fn leaf(&self, key: &AsciiFingerprint) -> Option<&Node<V>> {
+        let mut node = &self.root;
+        for count in key.as_ref() {
+            node = node.child(*count)?;
+        }
+        Some(node)
+    }
+}
+
+impl<V: Clone> Store<AsciiFingerprint, V> for SubsetIndex<V> {
+    fn remove(&mut self, key: &AsciiFingerprint) {
+        let removed = self.root.modify_leaf(key.as_ref(), |values| {
+            let len = values.len();
+            values.clear();
+            len
+        });
+        self.len -= removed.unwrap_or(0);
+    }
+
+    fn get_items(&self, key: &AsciiFingerprint) -> Vec<V> {
+        self.leaf(key).map(|node| node.values.clone()).unwrap_or_default()
+    }
+
+    fn remove_item(&mut self, key: &AsciiFingerprint, idx: usize) {
+        let removed = self.root.modify_leaf(key.as_ref(), |values| {
+            if idx < values.len() {
+                values.remove(idx);
+                true
+            } else {
+                false
+            }
+        });
+        if removed == Some(true) {
+            self.len -= 1;
+        }
+    }
+
+    fn insert(&mut self, key: AsciiFingerprint, value: V) {
+        debug_assert_eq!(key.as_ref().len(), ALPHABET_LEN);
+        let mut node = &mut self.root;
+        for count in key.as_ref() {
+            node = node.child_mut(*count);
+        }
+        let before = node.values.len();
+        self.policy.add(&mut node.values, value);
+        self.len = self.len + node.values.len() - before;
+    }
+}
+
+impl<V> Default for SubsetIndex<V> {
+    fn default() -> Self {
+        SubsetIndex::new()
+    }
+}
+
+/// Handles a single item, as with `process_item`, and also reports each
+/// stored item whose letters are a strict subset of this item's letters,
+/// and which has at least `min_letters` letters.
+///
+/// Subset matches are reported with `Adapter::handle_subset_match`; they
+/// are not removed from the index.
+pub fn process_item_subsets<T, A, TE>(item: T,
+                                      index: &mut SubsetIndex<T>,
+                                      min_letters: usize,
+                                      adapter: &mut A,
+                                      tester: &mut TE)
+    where T: AsStr + Clone,
+          A: Adapter<T, TE>,
+          TE: Tester<T, Fingerprint=AsciiFingerprint>,
+{
+    let ident = tester.fingerprint(&item);
+    for candidate in index.subsets(&ident, min_letters) {
+        let remainder = ident.subtract(&tester.fingerprint(candidate))
+            .expect("subset query returned a non-subset");
+        adapter.handle_subset_match(&item, candidate, &remainder);
+    }
+    process_item(item, index, adapter, tester)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anagrams::{AsciiTester, SimpleAdapter};
+
+    fn fingerprint(s: &str) -> AsciiFingerprint {
+        AsciiFingerprint::new(s)
+    }
+
+    #[test]
+    fn subsets() {
+        let mut index = SubsetIndex::new();
+        for word in ["cat", "act", "at", "tack", "dog", "cart", "a"].iter() {
+            index.insert(fingerprint(word), *word);
+        }
+        assert_eq!(index.len(), 7);
+
+        let mut found = index.subsets(&fingerprint("attack"), 0);
+        found.sort();
+        assert_eq!(found, vec![&"a", &"act", &"at", &"cat", &"tack"]);
+
+        // only strict subsets
+        let mut found = index.subsets(&fingerprint("cat"), 0);
+        found.sort();
+        assert_eq!(found, vec![&"a", &"at"]);
+
+        let mut found = index.subsets(&fingerprint("attack"), 3);
+        found.sort();
+        assert_eq!(found, vec![&"act", &"cat", &"tack"]);
+        assert!(index.subsets(&fingerprint("xyz"), 0).is_empty());
+    }
+
+    #[test]
+    fn store() {
+        let mut index = SubsetIndex::new();
+        index.insert(fingerprint("cat"), "cat");
+        index.insert(fingerprint("act"), "act");
+        index.insert(fingerprint("dog"), "dog");
+        assert_eq!(index.get_items(&fingerprint("tac")), vec!["cat", "act"]);
+
+        index.remove_item(&fingerprint("tac"), 0);
+        assert_eq!(index.get_items(&fingerprint("tac")), vec!["act"]);
+        index.remove_item(&fingerprint("tac"), 0);
+        assert!(index.get_items(&fingerprint("tac")).is_empty());
+        assert_eq!(index.len(), 1);
+
+        index.remove(&fingerprint("god"));
+        assert!(index.is_empty());
+        assert!(index.root.is_empty());
+    }
+
+    #[test]
+    fn process_subsets() {
+        let mut adapter = SimpleAdapter::new();
+        let mut tester = AsciiTester::default();
+        let mut index = SubsetIndex::new();
+        process_item_subsets("a cat", &mut index, 4, &mut adapter, &mut tester);
+        process_item_subsets("hello", &mut index, 4, &mut adapter, &mut tester);
+        process_item_subsets("the", &mut index, 4, &mut adapter, &mut tester);
+        process_item_subsets("hello there", &mut index, 4, &mut adapter, &mut tester);
+        // "the" is too short
+        assert_eq!(adapter.subset_hits(), &[("hello there", "hello")]);
+        assert_eq!(index.len(), 4);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use bincode;
+use flate2::Crc;
+
+use logchunk::{read_or_eof, zeroed_to_end};
+use mdbm::MdbmError;
+
+/// A record header: the length of the record, and a checksum of the
+/// length and the record.
+const HEADER_LEN: u64 = 12;
+const LENGTH_LEN: usize = 8;
+
+/// The latest contents of each bucket in a log; `None` if it was removed.
+pub type Buckets<V> = HashMap<Vec<u8>, Option<Vec<V>>>;
+
+/// A log of changes to the buckets in a `ChunkedStore`'s cache.
+///
+/// Each record holds the new contents of a bucket, so replaying the log
+/// recovers every bucket changed since the log was last cleared.
+///
+/// If the last record was only partially written, or is damaged, it is
+/// discarded on opening.
+pub struct WriteAheadLog {
+    file: File,
+    /// The end of the last complete record.
+    len: u64,
+}
+
+impl WriteAheadLog {
+    /// Opens or creates the log at `path`, returning it along with the
+    /// buckets it records.
+    pub fn open<V: DeserializeOwned>(path: &Path) -> Result<(Self, Buckets<V>), MdbmError> {
+        let file = OpenOptions::new().read(true).write(true).create(true)
+            .truncate(false).open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut buckets = Buckets::new();
+        let mut len = 0;
+        {
+            let mut reader = BufReader::new(&file);
+            let mut header = [0; HEADER_LEN as usize];
+            loop {
+                if !read_or_eof(&mut reader, &mut header)? { break }
+                let (record_len, checksum): (u64, u32) = bincode::deserialize(&header)?;
+                // don't trust the length of a partial record
+                let end = match record_len.checked_add(HEADER_LEN).and_then(|n| n.checked_add(len)) {
+                    Some(end) if end <= file_len => end,
+                    _ => break,
+                };
+                let mut record = vec![0; record_len as usize];
+                reader.read_exact(&mut record)?;
+                let decoded = if checksum == record_checksum(&header[..LENGTH_LEN], &record) {
+                    bincode::deserialize::<(Vec<u8>, Option<Vec<V>>)>(&record).ok()
+                } else {
+                    None
+                };
+                match decoded {
+                    Some((key, bucket)) => {
+                        buckets.insert(key, bucket);
+                        len = end;
+                    }
+                    None => {
+                        // a crash can leave a damaged or zeroed last record
+                        let zeroed = header.iter().chain(&record).all(|&b| b == 0)
+                            && zeroed_to_end(&mut reader)?;
+                        if end == file_len || zeroed { break }
+                        let msg = "corrupt record in write-ahead log";
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, msg).into());
+                    }
+                }
+            }
+        }
+        if len < file_len {
+            eprintln!("discarding partial record in {:?}", path);
+            file.set_len(len)?;
+        }
+        Ok((WriteAheadLog { file, len }, buckets))
+    }
+
+    /// Records the new contents of a bucket, or `None` if it was removed.
+    pub fn append<V: Serialize>(&mut self, key: &[u8], bucket: Option<&Vec<V>>)
+        -> Result<(), MdbmError>
+    {
+        let body = bincode::serialize(&(key, bucket))?;
+        let mut record = bincode::serialize(&(body.len() as u64))?;
+        let checksum = record_checksum(&record, &body);
+        record.extend(bincode::serialize(&checksum)?);
+        debug_assert_eq!(record.len() as u64, HEADER_LEN);
+        record.extend(body);
+        self.file.seek(SeekFrom::Start(self.len))?;
+        // a single write, so that a crash leaves at most one partial record
+        self.file.write_all(&record)?;
+        self.len += record.len() as u64;
+        Ok(())
+    }
+
+    /// The size of the log, in bytes.
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+
+    /// Empties the log, once everything in it has been written to a chunk.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+fn record_checksum(length: &[u8], record: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(length);
+    crc.update(record);
+    crc.sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let tempdir = TempDir::new("wal_test").unwrap();
+        let path = tempdir.path().join("cache.wal");
+        {
+            let (mut wal, buckets) = WriteAheadLog::open::<i32>(&path).unwrap();
+            assert!(buckets.is_empty());
+            wal.append(b"one", Some(&vec![1])).unwrap();
+            wal.append(b"two", Some(&vec![2])).unwrap();
+            wal.append(b"one", Some(&vec![1, 11])).unwrap();
+            wal.append::<i32>(b"two", None).unwrap();
+        }
+
+        // simulate a crash partway through writing a record
+        let full_len = path.metadata().unwrap().len();
+        OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(&[40, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+
+        let (mut wal, buckets) = WriteAheadLog::open::<i32>(&path).unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[&b"one".to_vec()], Some(vec![1, 11]));
+        assert_eq!(buckets[&b"two".to_vec()], None);
+        assert_eq!(wal.size(), full_len);
+        assert_eq!(path.metadata().unwrap().len(), full_len);
+
+        wal.clear().unwrap();
+        let (_, buckets) = WriteAheadLog::open::<i32>(&path).unwrap();
+        assert!(buckets.is_empty());
+    }
+
+    #[test]
+    fn damaged_tail() {
+        let tempdir = TempDir::new("wal_test").unwrap();
+        let path = tempdir.path().join("cache.wal");
+        {
+            let (mut wal, _) = WriteAheadLog::open::<i32>(&path).unwrap();
+            wal.append(b"one", Some(&vec![1])).unwrap();
+        }
+        let full_len = path.metadata().unwrap().len();
+        let append = |bytes: &[u8]| {
+            OpenOptions::new().append(true).open(&path).unwrap().write_all(bytes).unwrap();
+        };
+
+        // a crash can leave the end of the file zeroed
+        append(&[0; 16]);
+        let (_, buckets) = WriteAheadLog::open::<i32>(&path).unwrap();
+        assert_eq!(buckets[&b"one".to_vec()], Some(vec![1]));
+        assert_eq!(path.metadata().unwrap().len(), full_len);
+
+        // or with a length that can't be right
+        append(&[0xff; 16]);
+        let (_, buckets) = WriteAheadLog::open::<i32>(&path).unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(path.metadata().unwrap().len(), full_len);
+
+        // or with a record that fails its checksum
+        append(&[4, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let (_, buckets) = WriteAheadLog::open::<i32>(&path).unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(path.metadata().unwrap().len(), full_len);
+
+        // but a damaged record followed by others is an error
+        let other_path = tempdir.path().join("other.wal");
+        WriteAheadLog::open::<i32>(&other_path).unwrap().0
+            .append(b"two", Some(&vec![2])).unwrap();
+        append(&[4, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        append(&fs::read(&other_path).unwrap());
+        assert!(WriteAheadLog::open::<i32>(&path).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+// Pairs of items with the same fingerprint, and whether `AsciiTester`
+// should consider them a match. Shared by the `is_match` benchmark
+// and the regression tests.
+pub const IS_MATCH_INPUTS: &[(&str, &str, bool)] = &[
+    ("I am so over this.", "I am so over this! 😒", false),
+    ("I wish some nights lasted forever.", "I wish some nights lasted forever.", false),
+    ("today i got a million, tomorrow i dont know", "Today I got a million, tomorrow I don't know🎶🎧", false),
+    ("You still like him, so stop lying to yourself.", "You still like him, so stop lying to yourself.", false),
+    ("I hate my life so much 💀", "I HATE MY LIFE SO MUCH", false),
+    ("i want to be in LOVE !", "I want to be in love 🙃", false),
+    ("I just need you here", "I just need you here", false),
+    ("A mind troubled by doubt cannot focus on the course to victory. - Arthur Golden", "A mind troubled by doubt cannot focus on the course to victory. - Arthur Golden", false),
+    ("Our Wellness Doctors are highly experienced,trained by a specialist from Australia.Unique diagnostic methods are used for health evaluation.", "Our Wellness Doctors are highly experienced,trained by a specialist from Australia.Unique diagnostic methods are used for health evaluation.", false),
+    ("DamnItsTrue\nfb\nTough time never last, but but but tough people do. .............. DamnItsTrue fb", "DamnItsTrue\nfb\nTough time never last, but but but tough people do. .............. DamnItsTrue fb", false),
+    ("pretamanger\nyummylish\nChristmas sandwiches have arrived in pretamanger this makes me happy yummylish", "pretamanger\nyummylish\nChristmas sandwiches have arrived in pretamanger this makes me happy yummylish", false),
+    ("Cause girl you're perfect, you're always worth it", "cause, girl, you're perfect, you're always worth it", false),
+    ("Fairytales dont always have a happy ending do they..", "fairy tales don't always have a happy ending, do they?", false),
+    ("what would you do? // by cityhigh", "What Would You Do by City High 🙌🏻", false),
+    ("i don't like this", "I don't like this", false),
+    ("There is no way to happiness, happiness is the way.", "THERE IS NO WAY TO HAPPINESS. HAPPINESS IS THE WAY.", false),
+    ("I figure out you 😘 you figure out me 😘 we both a different breed 🤞🏾", "I figure out you, you figure out me we both a different breed", false),
+    ("I'm not perfect. I'll annoy you, make fun of you, say stupid things, but you'll never find someone who loves you as much as I do.", "I’m not perfect. I’ll annoy you, make fun of you, say stupid things, but you’ll never find someone who loves you as much as I do.:)", false),
+    ("I want to destroy everything.", "I want to destroy everything.", false),
+    ("I'm thinking about it", "i’m thinking about it", false),
+    ("Next track: Maiak - Sometimes You've Got To Take the Hardest Line #NowPlaying #postrock", "Next track: Maiak - Sometimes You've Got To Take the Hardest Line #NowPlaying #postrock", false),
+    ("Attitudes are contagious. Is yours worth catching? - Bruce Van Horn #quote", "Attitudes are contagious. Is yours worth catching? - Bruce Van Horn #quote", false),
+    ("nobody cares about me", "Nobody cares about me 😔", false),
+    ("Missing you already 😔", "missing you already", false),
+    ("\"This is who I am. Nobody said you had to like it.\"", "This is who I am. Nobody said you had to like it 🙄🖕🏻", false),
+    ("Gyah! Wh-what do you want?!", "Gyah! Wh-what do you want?!", false),
+    ("It's half past eleven.\nT'eh lieh oor lurg nane jeig.\nIt's quarter to twelve.\nT'eh kerroo gys daa-yeig.", "It's half past eleven.\nT'eh lieh oor lurg nane jeig.\nIt's quarter to twelve.\nT'eh kerroo gys daa-yeig.", false),
+    ("you dont know lol", "Lol you don’t know", false),
+    ("*sings along to The Eagles*\n\nKaraoke has changed my life!", "*sings along to The Eagles*\n\nKaraoke has changed my life!", false),
+    ("Bad day not a bad life", "Bad day not a bad life 😐", false),
+    ("wish I knew what's wrong with me.", "wish i knew whats wrong with me", false),
+    ("Maher Zain - Forgive Me", "Maher Zain - Forgive Me", false),
+    ("You're welcome.\nYou're very welcome.\nDon't mention it.\nNo problem.\nNo worries.\nOwa it ano man.\nIndi mo eon pagmitla-ngon.", "You're welcome.\nYou're very welcome.\nDon't mention it.\nNo problem.\nNo worries.\nOwa it ano man.\nIndi mo eon pagmitla-ngon.", false),
+    ("How 'bout a round of applause👏🏻👏🏻👏🏻", "How ‘bout a round of applause 👏🏻", false),
+    ("Don't take this the wrong way but maybe this time we don't drive the truck.", "Don't take this the wrong way but maybe this time we don't drive the truck.", false),
+    ("someone talk to me.", "Someone talk to me", false),
+    ("I just hope you miss me too.", "I just hope you miss me too...", false),
+    ("Onew Forever Love...", "Onew Forever Love...", false),
+    ("Why did the lightbulb cross the road? To get to the dark side.", "Why did the lightbulb cross the road? To get to the dark side.", false),
+    ("My Heart by Paramore 👌💘", "My Heart by Paramore 😍😍", false),
+    ("Sometimes we expect to much from others, because we would be willing to do that much for them.", "Sometimes we expect to much from others, because we would be willing to do that much for them.", false),
+    ("Like for something nice 🤘😊", "Like for something nice 🌕", false),
+    ("We lose ourselves in the things we love. We find ourselves there, too.", "We lose ourselves in the things we love. We find ourselves there, too.", false),
+    ("its a beautiful day in the neighborhood", "it's a beautiful day in the neighborhood", false),
+    ("I feel unimportant..", "i feel unimportant", false),
+    ("Now playing pitbull - dont stop the party.mp3 by !", "Now playing pitbull - dont stop the party.mp3 by !", false),
+    ("Love truth, and pardon error. - Voltaire", "Love truth, and pardon error. - Voltaire", false),
+    ("It's in me lil nigga . I keep that semi lil nigga", "It's in me lil nigga . I keep that semi lil nigga", false),
+    ("Back like I never left", "Back like I never left 💯", false),
+    ("A lion doesn’t concern himself with the opinions of a sheep.", "A lion doesn't concern himself with the opinions of a sheep.", false),
+    ("I love baseball", "i love baseball", false),
+    ("I'm not just sure, I'm HIV positive.", "I'm not just sure, I'm HIV positive.", false),
+    ("Good morning..!", "Good morning☺️", false),
+    ("Friends: Can I come over? Real Friends: I'm coming over.", "Friends: Can I come over? Real Friends: I’m coming over..", false),
+    ("Take |silly staff\" photos. Have som. fun!", "Take \"silly staf.\" photos. Have somf fun!", false),
+    ("Headin' to Salt Lake City, Utah ? $50 Free Lyft credit w/ Lyft Coupon Code PIP #freeLyft #Lyftcoupon", "Headin' to Salt Lake City, Utah ? $50 Free Lyft credit w/ Lyft Coupon Code PIP #freeLyft #Lyftcoupon", false),
+    ("I know you playing games.", "I know you playing games🎧🎧", false),
+    ("\"He was no dragon. Fire cannot kill a dragon\"", "\"He was no dragon. Fire cannot kill a dragon.\"", false),
+    ("I think a part of me will always be waiting for you", "I think a part of me will always be waiting for you.", false),
+    ("Gimme another hour or two, hour with you", "gimme another hour or two, hour with you", false),
+    ("Have a purpose.", "Have a purpose.", false),
+    ("\"I'm in pain, wanna put 10 shots in my brain\nI've been trippin' 'bout some things, can't change\nSuicidal, same time I'm tame\"", "I'm in pain, wanna put 10 shots in my brain\nI've been trippin' 'bout some things, can't change\nSuicidal, same time I'm tame", false),
+    ("I hate waiting", "I hate waiting 😒", false),
+    ("Otw to tagaytay", "Otw to Tagaytay📍", false),
+    ("It's been a hard day's night~ 🎶", "It's been a hard day's night", false),
+    ("It's always too good to be true", "It's always too good to be true👌🏽", false),
+    ("lowkey maybe highkey", "Low key. Maybe high key.", false),
+    ("Sometimes music speaks what you feel inside.😌❣", "Sometimes music speaks what you feel inside.", false),
+    ("I keep thinking I have school tomorrow😂", "I keep thinking i have school tomorrow", false),
+    ("Monster truck with big wheels. I am so angry. I want to burst into tears.", "Monster truck with big wheels. I am so angry. I want to burst into tears.", false),
+    ("I hate being sick :(", "I hate being sick", false),
+    ("thinkin of a master plan 🤔", "Thinkin of a master plan", false),
+    ("When in doubt, tell the truth. - Mark Twain", "When in doubt, tell the truth. - Mark Twain", false),
+    ("I’m in pain , wanna put 10 shots to my brain", "I'm in pain, wanna put 10 shots to my brain", false),
+    ("And then one day you find ten years have got behind you\nNo one told you when to run, you missed the starting gun", "And then one day you find ten years have got behind you\nNo one told you when to run, you missed the starting gun", false),
+    ("I’m so annoyed by him", "Hi my name is nobody", true),
+    ("Go for it. Fuck.", "fUCK I forgot", true),
+    ("mine istomorrow", "tomorrow is mine", false),
+    ("That shirt hurted.", "its the hard truth", true),
+    ("My cheeks need clappin", "Check my pinned please!", true),
+    ("Feel so unfair", "I suffer alone...", true),
+];
@@ -0,0 +1 @@
+pub mod is_match;
@@ -0,0 +1,25 @@
+extern crate manga_rs;
+
+use manga_rs::{AsciiTester, Tester};
+
+mod data;
+
+use data::is_match::IS_MATCH_INPUTS;
+
+#[test]
+fn is_match_regressions() {
+    let mut tester = AsciiTester::default();
+    let failures = IS_MATCH_INPUTS.iter()
+        .filter(|&&(one, two, expected)| tester.is_match(&one, &two) != expected)
+        .map(|&(one, two, expected)| format!("expected {}: {:?} / {:?}", expected, one, two))
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn inputs_share_fingerprints() {
+    let mut tester = AsciiTester::default();
+    for &(one, two, _) in IS_MATCH_INPUTS {
+        assert_eq!(tester.fingerprint(&one), tester.fingerprint(&two), "{:?} / {:?}", one, two);
+    }
+}